@@ -0,0 +1,143 @@
+extern crate COST;
+
+use COST::compression;
+use COST::generators::splitmix64;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+use std::io::BufReader;
+
+// each vertex gets a HyperLogLog counter with 2^LOG_REGISTERS one-byte registers.
+// 32 registers gives a relative standard deviation of about 1.04 / sqrt(32) ~ 18%
+// per counter, which averages out considerably once summed over all vertices.
+const LOG_REGISTERS: usize = 5;
+const REGISTERS: usize = 1 << LOG_REGISTERS;
+
+fn main() {
+
+    if std::env::args().len() != 4 {
+        println!("Usage: anf  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes");
+        return;
+    }
+
+    let mode = std::env::args().nth(1).expect("mode unavailable");
+    let name = std::env::args().nth(2).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+
+    match mode.as_str() {
+        "vertex" => {
+            anf(&NodesEdgesMemMapper::new(&name).or_exit("anf"), nodes)
+        },
+        "hilbert" => {
+            anf(&UpperLowerMemMapper::new(&name).or_exit("anf"), nodes)
+        },
+        "zorder" => {
+            anf(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("anf"), nodes)
+        },
+        "compressed" => {
+            anf(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("anf"))), nodes)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("anf");
+            anf(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("anf"), nodes)
+        },
+        "varint" => {
+            anf(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("anf"), nodes)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); },
+    }
+}
+
+// HyperANF (Boldi, Rosa, Vigna): the ball of radius t+1 around x is x together with the
+// balls of radius t around each out-neighbor of x, so one scan of the edges per radius
+// suffices if each ball is summarized by a HyperLogLog counter, and union is register-wise max.
+fn anf<G: EdgeMapper>(graph: &G, nodes: u32) {
+
+    let timer = std::time::Instant::now();
+
+    let mut old = vec![0u8; nodes as usize * REGISTERS];
+    for node in 0 .. nodes {
+        let hash = splitmix64(node as u64);
+        let register = (hash as usize) & (REGISTERS - 1);
+        let rank = (((hash >> LOG_REGISTERS) | (1 << (64 - LOG_REGISTERS))).trailing_zeros() + 1) as u8;
+        old[node as usize * REGISTERS + register] = rank;
+    }
+    let mut new = old.clone();
+
+    // neighborhood[t] estimates the number of pairs (x, y) with y reachable from x in at most t steps.
+    let mut neighborhood = vec![estimate_total(&old)];
+    println!("{:?}\tN({}): {}", timer.elapsed(), 0, neighborhood[0]);
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        graph.map_edges(|x, y| {
+            let x = x as usize * REGISTERS;
+            let y = y as usize * REGISTERS;
+            for register in 0 .. REGISTERS {
+                if new[x + register] < old[y + register] {
+                    new[x + register] = old[y + register];
+                    changed = true;
+                }
+            }
+        });
+
+        old.copy_from_slice(&new[..]);
+        if changed {
+            neighborhood.push(estimate_total(&old));
+            println!("{:?}\tN({}): {}", timer.elapsed(), neighborhood.len() - 1, neighborhood[neighborhood.len() - 1]);
+        }
+    }
+
+    // distances are only meaningful between distinct reachable pairs, so N(0) is subtracted out.
+    let base = neighborhood[0];
+    let total = neighborhood[neighborhood.len() - 1] - base;
+
+    let mut average = 0.0;
+    for t in 1 .. neighborhood.len() {
+        average += t as f64 * (neighborhood[t] - neighborhood[t-1]);
+    }
+    if total > 0.0 { average /= total; }
+
+    // effective diameter: the (interpolated) distance within which 90% of reachable pairs lie.
+    let mut effective = 0.0;
+    for t in 1 .. neighborhood.len() {
+        let reached = neighborhood[t] - base;
+        if reached >= 0.9 * total {
+            let before = neighborhood[t-1] - base;
+            effective = (t - 1) as f64 + (0.9 * total - before) / (reached - before);
+            break;
+        }
+    }
+
+    println!("diameter (lower bound): {}", neighborhood.len() - 1);
+    println!("effective diameter: {:.3}", effective);
+    println!("average distance: {:.3}", average);
+    println!("reachable pairs: {:.0}", neighborhood[neighborhood.len() - 1]);
+}
+
+// sums the HyperLogLog estimates of all counters in `registers`.
+fn estimate_total(registers: &[u8]) -> f64 {
+    let m = REGISTERS as f64;
+    let alpha = match REGISTERS {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _  => 0.7213 / (1.0 + 1.079 / m),
+    };
+
+    let mut total = 0.0;
+    for counter in registers.chunks(REGISTERS) {
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &register in counter {
+            sum += 0.5f64.powi(register as i32);
+            if register == 0 { zeros += 1; }
+        }
+
+        let estimate = alpha * m * m / sum;
+        total += if estimate <= 2.5 * m && zeros > 0 { m * (m / zeros as f64).ln() } else { estimate };
+    }
+
+    total
+}
@@ -0,0 +1,104 @@
+extern crate COST;
+
+use COST::generators::XorShift;
+use COST::graph_iterator::{NodesEdgesMemMapper, NodesEdgesIndex};
+use COST::report::OrExit;
+
+fn main() {
+
+    if std::env::args().len() != 6 {
+        println!("Usage: betweenness <prefix> nodes <samples> <seed> <top>");
+        return;
+    }
+
+    let name = std::env::args().nth(1).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(2).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let samples: u32 = std::env::args().nth(3).expect("samples unavailable").parse().expect("samples not parseable");
+    let seed: u64 = std::env::args().nth(4).expect("seed unavailable").parse().expect("seed not parseable");
+    let top: usize = std::env::args().nth(5).expect("top unavailable").parse().expect("top not parseable");
+
+    let graph = NodesEdgesMemMapper::new(&name).or_exit("betweenness");
+    let centrality = betweenness(&graph.index(nodes).or_exit("betweenness"), nodes, samples, seed);
+
+    let mut ranked: Vec<u32> = (0..nodes).collect();
+    ranked.sort_by(|&x, &y| centrality[y as usize].partial_cmp(&centrality[x as usize]).unwrap().then(x.cmp(&y)));
+    for &node in ranked.iter().take(top) {
+        println!("{}\t{}", node, centrality[node as usize]);
+    }
+}
+
+// Brandes' algorithm, run from `samples` distinct sources chosen uniformly at random and scaled up
+// by nodes / samples to estimate betweenness centrality over directed shortest paths.
+fn betweenness(graph: &NodesEdgesIndex, nodes: u32, samples: u32, seed: u64) -> Vec<f64> {
+
+    let timer = std::time::Instant::now();
+
+    let mut rng = XorShift::new(seed);
+    let samples = std::cmp::min(samples, nodes);
+
+    let mut centrality = vec![0f64; nodes as usize];
+
+    // per-source state; only entries for visited vertices are reset between sources.
+    let mut distance = vec![u32::MAX; nodes as usize];
+    let mut paths = vec![0f64; nodes as usize];
+    let mut dependency = vec![0f64; nodes as usize];
+    let mut order = Vec::new();
+
+    let mut chosen = vec![false; nodes as usize];
+    for sample in 0 .. samples {
+
+        let mut source = rng.below(nodes as u64) as u32;
+        while chosen[source as usize] { source = rng.below(nodes as u64) as u32; }
+        chosen[source as usize] = true;
+
+        // breadth-first search from source, counting shortest paths to each vertex.
+        distance[source as usize] = 0;
+        paths[source as usize] = 1.0;
+        order.push(source);
+        let mut cursor = 0;
+        while cursor < order.len() {
+            let node = order[cursor];
+            cursor += 1;
+            for &next in graph.neighbors(node) {
+                if distance[next as usize] == u32::MAX {
+                    distance[next as usize] = distance[node as usize] + 1;
+                    order.push(next);
+                }
+                if distance[next as usize] == distance[node as usize] + 1 {
+                    paths[next as usize] += paths[node as usize];
+                }
+            }
+        }
+
+        // accumulate dependencies in reverse order; successors on shortest paths are exactly
+        // the out-neighbors one step further from the source, so no predecessor lists are needed.
+        for &node in order.iter().rev() {
+            let mut total = 0f64;
+            for &next in graph.neighbors(node) {
+                if distance[next as usize] == distance[node as usize] + 1 {
+                    total += paths[node as usize] / paths[next as usize] * (1.0 + dependency[next as usize]);
+                }
+            }
+            dependency[node as usize] = total;
+            if node != source { centrality[node as usize] += total; }
+        }
+
+        for &node in &order {
+            distance[node as usize] = u32::MAX;
+            paths[node as usize] = 0.0;
+            dependency[node as usize] = 0.0;
+        }
+        order.clear();
+
+        if (sample + 1) % 100 == 0 || sample + 1 == samples {
+            println!("{:?}\tsources: {}", timer.elapsed(), sample + 1);
+        }
+    }
+
+    if samples > 0 {
+        let scale = nodes as f64 / samples as f64;
+        for score in centrality.iter_mut() { *score *= scale; }
+    }
+
+    centrality
+}
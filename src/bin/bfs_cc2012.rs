@@ -1,39 +1,58 @@
 extern crate COST;
 
-use std::fs::File;
-
-use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, NodesEdgesMemMapper, UpperLowerMemMapper };
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::typedrw::TypedMemoryMap;
+use COST::report::{Format, OrExit, Report};
 use std::io::BufReader;
 
 fn main() {
 
-    if std::env::args().len() != 4 {
-        println!("Usage: bfs  (vertex | hilbert | compressed) <prefix> nodes");
+    let mut args: Vec<String> = std::env::args().collect();
+    let format = Format::extract(&mut args);
+
+    if args.len() != 4 {
+        println!("Usage: bfs  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes [--format (text | json)]");
         return;
     }
 
-    let mode = std::env::args().nth(1).expect("mode unavailable");
-    let name = std::env::args().nth(2).expect("name unavailable");
-    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let mode = args[1].clone();
+    let name = args[2].clone();
+    let nodes: u32 = args[3].parse().expect("nodes not parseable");
+
+    let mut report = Report::new(format, "bfs_cc2012", &mode, &name);
+    report.set("nodes", nodes);
 
     match mode.as_str() {
         "vertex" => {
-            bfs(&NodesEdgesMemMapper::new(&name), nodes)
+            bfs(&NodesEdgesMemMapper::new(&name).or_exit("bfs_cc2012"), nodes, &mut report)
         },
         "hilbert" => {
-            bfs(&UpperLowerMemMapper::new(&name), nodes)
+            bfs(&UpperLowerMemMapper::new(&name).or_exit("bfs_cc2012"), nodes, &mut report)
+        },
+        "zorder" => {
+            bfs(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("bfs_cc2012"), nodes, &mut report)
         },
         "compressed" => {
-            bfs(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&name).unwrap())), nodes)
+            bfs(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("bfs_cc2012"))), nodes, &mut report)
         },
-        _ => { println!("unrecognized mode: {:?}", mode); },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("bfs_cc2012");
+            bfs(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("bfs_cc2012"), nodes, &mut report)
+        },
+        "varint" => {
+            bfs(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("bfs_cc2012"), nodes, &mut report)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
     }
+
+    report.finish();
 }
 
 // NOTE : The following code is specific to the common crawl 2012 dataset.
 // NOTE : It may behave very badly indeed with other datasets.
 
-fn bfs<G: EdgeMapper>(graph: &G, nodes: u32) {
+fn bfs<G: EdgeMapper>(graph: &G, nodes: u32, report: &mut Report) {
 
     let timer = std::time::Instant::now();
 
@@ -44,8 +63,11 @@ fn bfs<G: EdgeMapper>(graph: &G, nodes: u32) {
     let mut label = vec![65535u16; nodes as usize];
     label[0] = 0;
 
+    let mut edges = 0u64;
     graph.map_edges(|mut x, mut y| {
 
+        edges += 1;
+
         if x == 0 { label[y as usize] = 1; }
         if y == 0 { label[x as usize] = 1; }
 
@@ -73,7 +95,9 @@ fn bfs<G: EdgeMapper>(graph: &G, nodes: u32) {
         // else                      { roots[i as usize] = i; }
     }
 
-    println!("{:?}\titeration: {}", timer.elapsed(), 0);
+    if report.is_text() { println!("{:?}\titeration: {}", timer.elapsed(), 0); }
+    report.iteration();
+    report.set("edges", edges);
 
     // WTF is this? What are YOU PLANNNING?!??!
     let mut edges = Vec::new();
@@ -111,7 +135,8 @@ fn bfs<G: EdgeMapper>(graph: &G, nodes: u32) {
         });
 
         iteration += 1;
-        println!("{:?}\titeration: {}", timer.elapsed(), iteration);
+        if report.is_text() { println!("{:?}\titeration: {}", timer.elapsed(), iteration); }
+        report.iteration();
     }
 
     let mut done = false;
@@ -136,14 +161,18 @@ fn bfs<G: EdgeMapper>(graph: &G, nodes: u32) {
         });
 
         iteration += 1;
-        println!("{:?}\titeration: {}", timer.elapsed(), iteration);
+        if report.is_text() { println!("{:?}\titeration: {}", timer.elapsed(), iteration); }
+        report.iteration();
     }
 
     let mut counts = vec![0u64; 1 << 16];
     for &x in &label { counts[x as usize] += 1; }
+    let mut distances = Vec::new();
     for (dist, count) in counts.iter().enumerate() {
         if *count > 0 {
-            println!("counts[{}]: {}", dist, count);
+            if report.is_text() { println!("counts[{}]: {}", dist, count); }
+            distances.push(vec![dist as u64, *count]);
         }
     }
+    report.result("counts", distances);
 }
\ No newline at end of file
@@ -0,0 +1,105 @@
+extern crate COST;
+
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+use std::io::BufReader;
+
+fn main() {
+
+    if std::env::args().len() != 4 {
+        println!("Usage: coloring  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes");
+        return;
+    }
+
+    let mode = std::env::args().nth(1).expect("mode unavailable");
+    let name = std::env::args().nth(2).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+
+    match mode.as_str() {
+        "vertex" => {
+            coloring(&NodesEdgesMemMapper::new(&name).or_exit("coloring"), nodes)
+        },
+        "hilbert" => {
+            coloring(&UpperLowerMemMapper::new(&name).or_exit("coloring"), nodes)
+        },
+        "zorder" => {
+            coloring(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("coloring"), nodes)
+        },
+        "compressed" => {
+            coloring(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("coloring"))), nodes)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("coloring");
+            coloring(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("coloring"), nodes)
+        },
+        "varint" => {
+            coloring(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("coloring"), nodes)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); },
+    }
+}
+
+// Welsh-Powell greedy coloring: vertices in decreasing order of degree (ties by identifier) take the
+// smallest color not used by an already colored neighbor. Edge directions and self-loops are ignored.
+fn coloring<G: EdgeMapper>(graph: &G, nodes: u32) {
+
+    let timer = std::time::Instant::now();
+
+    // greedy coloring needs each vertex's neighbors together, so we build symmetric adjacency lists.
+    let mut offsets = vec![0usize; nodes as usize + 1];
+    graph.map_edges(|x, y| {
+        if x != y {
+            offsets[x as usize + 1] += 1;
+            offsets[y as usize + 1] += 1;
+        }
+    });
+    for index in 1 .. offsets.len() { offsets[index] += offsets[index - 1]; }
+
+    let mut cursor = offsets.clone();
+    let mut neighbors = vec![0u32; offsets[nodes as usize]];
+    graph.map_edges(|x, y| {
+        if x != y {
+            neighbors[cursor[x as usize]] = y;
+            cursor[x as usize] += 1;
+            neighbors[cursor[y as usize]] = x;
+            cursor[y as usize] += 1;
+        }
+    });
+
+    println!("{:?}\tadjacency built", timer.elapsed());
+
+    let degree = |node: u32| offsets[node as usize + 1] - offsets[node as usize];
+    let mut order: Vec<u32> = (0..nodes).collect();
+    order.sort_by(|&x, &y| degree(y).cmp(&degree(x)).then(x.cmp(&y)));
+
+    // forbidden[c] == node + 1 records that color c is taken by a neighbor of node.
+    let mut color = vec![u32::MAX; nodes as usize];
+    let mut forbidden: Vec<u32> = Vec::new();
+    let mut colors = 0;
+    for &node in &order {
+        for &neighbor in &neighbors[offsets[node as usize] .. offsets[node as usize + 1]] {
+            let taken = color[neighbor as usize];
+            if taken != u32::MAX {
+                while forbidden.len() <= taken as usize { forbidden.push(0); }
+                forbidden[taken as usize] = node + 1;
+            }
+        }
+
+        let mut choice = 0;
+        while (choice as usize) < forbidden.len() && forbidden[choice as usize] == node + 1 { choice += 1; }
+        color[node as usize] = choice;
+        colors = std::cmp::max(colors, choice + 1);
+    }
+
+    println!("{:?}\tcolored", timer.elapsed());
+
+    let mut violations = 0u64;
+    graph.map_edges(|x, y| {
+        if x != y && color[x as usize] == color[y as usize] { violations += 1; }
+    });
+
+    println!("{} colors used", colors);
+    println!("{} edges violated", violations);
+}
@@ -1,6 +1,5 @@
 #![allow(non_snake_case)]
 
-extern crate lz4;
 extern crate byteorder;
 
 extern crate docopt;
@@ -11,23 +10,43 @@ extern crate COST;
 use std::io::Write;
 use std::fs::File;
 
-use COST::hilbert_curve::{encode, Decoder, to_hilbert, merge};
+use COST::compression::{self, Input, Output};
+use COST::hilbert_curve::{encode, Decoder, to_hilbert, merge, merge_parallel};
 use COST::graph_iterator::ReaderMapper;
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
 use std::io::{BufReader, BufWriter, stdin, stdout};
 use byteorder::{WriteBytesExt, LittleEndian};
 
 static USAGE: &'static str = "
-Usage: compressed parse_to_hilbert
-       compressed merge <source>...
-       compressed scan
+Usage: compressed parse_to_hilbert [--lz4] [--lz4-input]
+       compressed merge [--lz4] [--threads <n>] <source>...
+       compressed scan [--lz4] [--lz4-input] [--offsets <path>]
+
+<source> files whose names end in .lz4 are LZ4-compressed, as is stdin with --lz4-input, and
+the output is with --lz4.
+
+merge combines sorted delta streams, dropping duplicates. With several threads, it maps the sources
+into memory (decompressing LZ4 sources there), indexes each one in a single pass, cuts the index
+range into parts of similar size sampled from all sources, and merges the parts in parallel,
+writing each out in order as it completes.
+
+scan reads a delta stream of (src << 32) + dst keys in sorted order and writes the varint
+adjacency format read by the varint mode: neighbor data to stdout and node offsets to <path>.
+
+Options:
+    --lz4               Compress the output with LZ4
+    --lz4-input         Decompress stdin with LZ4
+    --offsets <path>    File receiving the node offsets of scan [default: offsets]
+    --threads <n>       Threads merging disjoint index ranges [default: 1]
 ";
 
 fn main() {
     let args = Docopt::new(USAGE).and_then(|dopt| dopt.parse()).unwrap_or_else(|e| e.exit());
 
     if args.get_bool("parse_to_hilbert") {
-        let reader_mapper = ReaderMapper { reader: || BufReader::new(stdin())};
-        let mut writer = BufWriter::new(stdout());
+        let reader_mapper = ReaderMapper { reader: || BufReader::new(Input::new(stdin(), args.get_bool("--lz4-input")).unwrap()) };
+        let mut writer = Output::new(BufWriter::new(stdout()), args.get_bool("--lz4")).unwrap();
 
         let mut prev = 0u64;
         to_hilbert(&reader_mapper, |next| {
@@ -35,36 +54,43 @@ fn main() {
             COST::hilbert_curve::encode(&mut writer, next - prev);
             prev = next;
         });
+        writer.finish().unwrap();
     }
 
     if args.get_bool("merge") {
-        let mut writer = BufWriter::new(stdout());
-        let mut vector = Vec::new();
-        for &source in args.get_vec("<source>").iter() {
-            vector.push(Decoder::new(lz4::Decoder::new(BufReader::new(File::open(source).unwrap())).unwrap()));
+        let sources = args.get_vec("<source>");
+        let threads: usize = args.get_str("--threads").parse().expect("threads not parseable");
+        let mut writer = Output::new(BufWriter::new(stdout()), args.get_bool("--lz4")).unwrap();
+
+        if threads <= 1 {
+            let mut prev = 0u64;
+            merge(open_sources(&sources), |next| {
+                assert!(prev <= next);
+                if prev < next {
+                    encode(&mut writer, next - prev);
+                    prev = next;
+                }
+            });
         }
-
-        let mut prev = 0u64;
-        merge(vector, |next| {
-            assert!(prev <= next);
-            if prev < next {
-                encode(&mut writer, next - prev);
-                prev = next;
-            }
-        });
+        else {
+            let maps: Vec<TypedMemoryMap<u8>> = sources.iter().map(|&source| TypedMemoryMap::new(source.to_owned()).or_exit("compressed")).collect();
+            let slices: Vec<&[u8]> = maps.iter().map(|map| &map[..]).collect();
+            merge_parallel(&slices, threads, &mut writer).or_exit("compressed");
+        }
+        writer.finish().unwrap();
     }
 
     if args.get_bool("scan") {
 
         let mut bytes = 0u64;
-        let mut writer = BufWriter::new(stdout());
-        let mut offsets = BufWriter::new(File::create("offsets").unwrap());
+        let mut writer = Output::new(BufWriter::new(stdout()), args.get_bool("--lz4")).unwrap();
+        let mut offsets = BufWriter::new(File::create(args.get_str("--offsets")).unwrap());
         let mut buffer = Vec::new();
 
         let mut prev_edge = 0u64;
         let mut prev_node = 0u64;
 
-        for next in Decoder::new(BufReader::new(stdin())) {
+        for next in Decoder::new(BufReader::new(Input::new(stdin(), args.get_bool("--lz4-input")).unwrap())) {
 
             let node = next >> 32;
             let edge = next % (1 << 32);
@@ -95,5 +121,12 @@ fn main() {
 
         writer.write_all(&buffer[..]).unwrap();
         buffer.clear();
+        writer.finish().unwrap();
     }
 }
+
+fn open_sources(sources: &[impl AsRef<str>]) -> Vec<Decoder<BufReader<Input<File>>>> {
+    sources.iter().map(|source| {
+        Decoder::new(BufReader::new(compression::open(source.as_ref()).or_exit("compressed")))
+    }).collect()
+}
@@ -0,0 +1,187 @@
+#![allow(non_snake_case)]
+
+extern crate docopt;
+use docopt::Docopt;
+
+extern crate COST;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+
+static USAGE: &str = "
+Usage: cost_bench [options] <graph>...
+
+Each <graph> is <prefix>:<nodes>. The vertex mode reads <prefix>.nodes and <prefix>.edges,
+the hilbert mode <prefix>.upper and <prefix>.lower, the zorder mode <prefix>.zupper and
+<prefix>.zlower, the compressed and compressed-mmap modes
+<prefix>.compressed, and the varint mode <prefix>.varint and <prefix>.varint.offsets; modes whose
+files are missing are skipped.
+
+All times are taken with a warm page cache: each mode's files are read once, untimed, before
+anything is measured. Algorithm times are the `elapsed` time each run reports for itself, which
+excludes process start-up; the I/O time is a single in-process pass over the edges.
+
+Options:
+    --algorithms <list>     Comma-separated algorithm binaries [default: pagerank,union_find,label_prop]
+    --modes <list>          Comma-separated modes [default: vertex,hilbert,compressed]
+    --reps <n>              Repetitions of each run [default: 3]
+    --reference <file>      Reference results for other systems, one `<system> <algorithm> <graph> <cores> <seconds>`
+                            per line, where <graph> is the file name of the prefix; adds the COST of each system.
+";
+
+fn main() {
+    let args = Docopt::new(USAGE).and_then(|dopt| dopt.parse()).unwrap_or_else(|e| e.exit());
+
+    let algorithms: Vec<&str> = args.get_str("--algorithms").split(',').collect();
+    let modes: Vec<&str> = args.get_str("--modes").split(',').collect();
+    let reps: usize = args.get_str("--reps").parse().expect("reps not parseable");
+
+    let directory = std::env::current_exe().expect("current executable unavailable");
+    let directory = directory.parent().expect("executable directory unavailable");
+
+    println!("page cache: warm");
+    println!("{:<12}{:<16}{:<12}{:>12}{:>12}{:>12}{:>14}", "algorithm", "graph", "mode", "best (s)", "mean (s)", "I/O (s)", "peak RSS (MB)");
+
+    let mut results = Vec::new();
+    for graph in args.get_vec("<graph>") {
+        let mut split = graph.rsplitn(2, ':');
+        let nodes = split.next().expect("nodes unavailable");
+        let prefix = split.next().expect("graphs must be given as <prefix>:<nodes>");
+        let label = Path::new(prefix).file_name().map(|x| x.to_string_lossy().into_owned()).unwrap_or(prefix.to_owned());
+
+        for &mode in &modes {
+            let path = match mode {
+                "vertex" | "hilbert" | "zorder" => prefix.to_owned(),
+                "compressed" | "compressed-mmap" => format!("{}.compressed", prefix),
+                "varint" => format!("{}.varint", prefix),
+                _ => panic!("unrecognized mode: {:?}", mode),
+            };
+            let required = match mode {
+                "vertex" => format!("{}.nodes", prefix),
+                "hilbert" => format!("{}.upper", prefix),
+                "zorder" => format!("{}.zupper", prefix),
+                "varint" => format!("{}.varint.offsets", prefix),
+                _ => path.clone(),
+            };
+            if !Path::new(&required).exists() { continue; }
+
+            // warm the page cache, so that neither the algorithms nor the I/O pass pay for the first read.
+            scan_time(mode, &path);
+
+            let mut rows = Vec::new();
+            for &algorithm in &algorithms {
+                let mut times = Vec::new();
+                let mut peak_kb = 0u64;
+                for _ in 0 .. reps {
+                    let output = Command::new(directory.join(algorithm))
+                        .args([mode, &path, nodes, "--format", "json"])
+                        .output()
+                        .expect("failed to run algorithm");
+
+                    assert!(output.status.success(), "{} {} {} failed: {}", algorithm, mode, path, String::from_utf8_lossy(&output.stderr));
+                    let record = String::from_utf8_lossy(&output.stdout).into_owned();
+                    peak_kb = std::cmp::max(peak_kb, json_number(&record, "peak_rss_kb").unwrap_or(0.0) as u64);
+                    times.push(json_number(&record, "elapsed").expect("run record without elapsed time"));
+                }
+
+                let best = times.iter().cloned().fold(f64::INFINITY, f64::min);
+                let mean = times.iter().sum::<f64>() / times.len() as f64;
+                rows.push((algorithm, best, mean, peak_kb));
+                results.push((algorithm.to_owned(), label.clone(), best));
+            }
+
+            // the I/O pass runs last, under the same cache state as the algorithms.
+            let scan = scan_time(mode, &path);
+            for (algorithm, best, mean, peak_kb) in rows {
+                println!("{:<12}{:<16}{:<12}{:>12.3}{:>12.3}{:>12.3}{:>14.1}", algorithm, label, mode, best, mean, scan, peak_kb as f64 / 1024.0);
+            }
+        }
+    }
+
+    if !args.get_str("--reference").is_empty() {
+        let reference = read_reference(args.get_str("--reference"));
+
+        println!();
+        println!("{:<12}{:<16}{:<16}{:>12}{:>10}", "algorithm", "graph", "system", "single (s)", "COST");
+
+        // the COST compares each system against the best single-threaded time over all modes.
+        let mut best: Vec<(String, String, f64)> = Vec::new();
+        for (algorithm, graph, time) in results {
+            match best.iter_mut().find(|b| b.0 == algorithm && b.1 == graph) {
+                Some(b) => { if time < b.2 { b.2 = time; } },
+                None => best.push((algorithm, graph, time)),
+            }
+        }
+
+        for &(ref algorithm, ref graph, time) in &best {
+            let mut systems: Vec<&str> = reference.iter().filter(|r| &r.1 == algorithm && &r.2 == graph).map(|r| r.0.as_str()).collect();
+            systems.sort();
+            systems.dedup();
+            for system in systems {
+                let cost = reference.iter()
+                    .filter(|r| r.0 == system && &r.1 == algorithm && &r.2 == graph && r.4 < time)
+                    .map(|r| r.3)
+                    .min();
+                let cost = cost.map(|c| c.to_string()).unwrap_or("unbounded".to_owned());
+                println!("{:<12}{:<16}{:<16}{:>12.3}{:>10}", algorithm, graph, system, time, cost);
+            }
+        }
+    }
+}
+
+// I/O time: a single pass over the edges doing nothing with them, which bounds how fast any
+// scan-based algorithm could be in this mode.
+fn scan_time(mode: &str, path: &str) -> f64 {
+    let timer = Instant::now();
+    let mut edges = 0u64;
+    match mode {
+        "vertex" => NodesEdgesMemMapper::new(path).or_exit("cost_bench").map_edges(|_, _| edges += 1),
+        "hilbert" => UpperLowerMemMapper::new(path).or_exit("cost_bench").map_edges(|_, _| edges += 1),
+        "zorder" => UpperLowerMemMapper::from_files(&format!("{}.zupper", path), &format!("{}.zlower", path)).or_exit("cost_bench").map_edges(|_, _| edges += 1),
+        "compressed" => DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(path).or_exit("cost_bench"))).map_edges(|_, _| edges += 1),
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(path.to_owned()).or_exit("cost_bench");
+            DeltaCompressedSliceMapper::new(&map[..]).or_exit("cost_bench").map_edges(|_, _| edges += 1)
+        },
+        "varint" => VarintMemMapper::new(path, &format!("{}.offsets", path)).or_exit("cost_bench").map_edges(|_, _| edges += 1),
+        _ => panic!("unrecognized mode: {:?}", mode),
+    }
+    std::hint::black_box(edges);
+    timer.elapsed().as_secs_f64()
+}
+
+// (system, algorithm, graph, cores, seconds)
+fn read_reference(filename: &str) -> Vec<(String, String, String, u32, f64)> {
+    let mut result = Vec::new();
+    for line in BufReader::new(File::open(filename).expect("reference file unavailable")).lines() {
+        let line = line.expect("read error");
+        if line.starts_with('#') || line.trim().is_empty() { continue; }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert!(fields.len() == 5, "malformed reference line: {:?}", line);
+        result.push((
+            fields[0].to_owned(),
+            fields[1].to_owned(),
+            fields[2].to_owned(),
+            fields[3].parse().expect("malformed cores"),
+            fields[4].parse().expect("malformed seconds"),
+        ));
+    }
+    result
+}
+
+// extracts the first numeric value for `key` from a flat JSON record, as printed by `Report`.
+fn json_number(record: &str, key: &str) -> Option<f64> {
+    let pattern = format!("\"{}\":", key);
+    let start = record.find(&pattern)? + pattern.len();
+    let rest = &record[start ..];
+    let end = rest.find([',', '}']).unwrap_or(rest.len());
+    rest[.. end].trim().parse().ok()
+}
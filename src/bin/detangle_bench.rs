@@ -0,0 +1,93 @@
+extern crate COST;
+
+use std::io::BufReader;
+use std::time::Instant;
+
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedSliceMapper};
+use COST::hilbert_curve::{BytewiseCached, Decoder};
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+
+fn main() {
+
+    if std::env::args().len() != 2 && std::env::args().len() != 3 {
+        println!("Usage: detangle_bench <compressed> [repetitions]");
+        println!("NOTE: times scalar and batch detangling of the Hilbert indices in <compressed>, alone and within");
+        println!("      the compressed-mmap scan.");
+        return;
+    }
+
+    let name = std::env::args().nth(1).expect("name unavailable");
+    let reps: usize = std::env::args().nth(2).map(|x| x.parse().expect("repetitions not parseable")).unwrap_or(5);
+
+    let tangles: Vec<u64> = Decoder::new(BufReader::new(compression::open(&name).or_exit("detangle_bench"))).collect();
+    println!("{} indices decoded", tangles.len());
+
+    let mut scalar_best = f64::INFINITY;
+    let mut scalar_sum = 0u64;
+    for _ in 0 .. reps {
+        let timer = Instant::now();
+        let mut hilbert = BytewiseCached::new();
+        let mut sum = 0u64;
+        for &tangle in &tangles {
+            let (x, y) = hilbert.detangle(tangle);
+            sum = sum.wrapping_add(x as u64).wrapping_add((y as u64) << 32);
+        }
+        scalar_best = scalar_best.min(timer.elapsed().as_secs_f64());
+        scalar_sum = std::hint::black_box(sum);
+    }
+
+    let mut batch_best = f64::INFINITY;
+    let mut batch_sum = 0u64;
+    for _ in 0 .. reps {
+        let timer = Instant::now();
+        let mut hilbert = BytewiseCached::new();
+        let mut xs = vec![0u32; 1024];
+        let mut ys = vec![0u32; 1024];
+        let mut sum = 0u64;
+        for chunk in tangles.chunks(1024) {
+            hilbert.detangle_batch(chunk, &mut xs[.. chunk.len()], &mut ys[.. chunk.len()]);
+            for index in 0 .. chunk.len() {
+                sum = sum.wrapping_add(xs[index] as u64).wrapping_add((ys[index] as u64) << 32);
+            }
+        }
+        batch_best = batch_best.min(timer.elapsed().as_secs_f64());
+        batch_sum = std::hint::black_box(sum);
+    }
+
+    assert_eq!(scalar_sum, batch_sum, "scalar and batch detangling disagree");
+
+    // the compressed-mmap scan, which decodes deltas and detangles them in batches, timed against
+    // the same scan detangling each index as it is decoded.
+    let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("detangle_bench");
+    let mapper = DeltaCompressedSliceMapper::new(&map[..]).or_exit("detangle_bench");
+    let mut unbatched_best = f64::INFINITY;
+    let mut unbatched_sum = 0u64;
+    let mut scan_best = f64::INFINITY;
+    let mut scan_sum = 0u64;
+    for _ in 0 .. reps {
+        let timer = Instant::now();
+        let mut sum = 0u64;
+        mapper.map_edges_unbatched(|x, y| sum = sum.wrapping_add(x as u64).wrapping_add((y as u64) << 32));
+        unbatched_best = unbatched_best.min(timer.elapsed().as_secs_f64());
+        unbatched_sum = std::hint::black_box(sum);
+
+        let timer = Instant::now();
+        let mut sum = 0u64;
+        mapper.map_edges(|x, y| sum = sum.wrapping_add(x as u64).wrapping_add((y as u64) << 32));
+        scan_best = scan_best.min(timer.elapsed().as_secs_f64());
+        scan_sum = std::hint::black_box(sum);
+    }
+
+    assert_eq!(unbatched_sum, scalar_sum, "unbatched scan disagrees with detangling");
+    assert_eq!(scan_sum, scalar_sum, "batched scan disagrees with detangling");
+
+    let rate = |seconds: f64| tangles.len() as f64 / seconds / 1000000.0;
+    println!("scalar detangle:\t{:.4}s\t({:.1}M edges/s)", scalar_best, rate(scalar_best));
+    println!("batch detangle:\t{:.4}s\t({:.1}M edges/s)", batch_best, rate(batch_best));
+    println!("speedup:\t{:.2}x", scalar_best / batch_best);
+    println!("compressed-mmap scan, unbatched:\t{:.4}s\t({:.1}M edges/s)", unbatched_best, rate(unbatched_best));
+    println!("compressed-mmap scan, batched:\t{:.4}s\t({:.1}M edges/s)", scan_best, rate(scan_best));
+    println!("scan speedup:\t{:.2}x", unbatched_best / scan_best);
+}
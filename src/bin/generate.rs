@@ -0,0 +1,121 @@
+#![allow(non_snake_case)]
+
+extern crate docopt;
+use docopt::Docopt;
+
+extern crate COST;
+
+use std::io::Write;
+
+use COST::generators::{self, XorShift};
+use COST::hilbert_curve::{to_hilbert, convert_to_hilbert};
+use COST::report::OrExit;
+use COST::writers::{check_outputs, AtomicFile, VertexGraphWriter, HilbertGraphWriter, CompressedGraphWriter};
+
+static USAGE: &str = "
+Usage: generate rmat <scale> <edges> [options] <format> <output>
+       generate erdos_renyi <nodes> <edges> [options] <format> <output>
+       generate grid <width> <height> [options] <format> <output>
+       generate power_law <nodes> <edges> [options] <format> <output>
+
+Writes the generated graph in <format>:
+    text          <output> with one `src dst` pair per line
+    vertex        <output>.nodes and <output>.edges, as written by to_vertex
+    hilbert       <output>.upper and <output>.lower, as written by to_hilbert
+    compressed    <output>, as written by compressed parse_to_hilbert, without duplicate
+                  edges or a self-loop at zero, which it cannot represent
+
+Options:
+    --seed <seed>           Seed for the random generators [default: 0]
+    --exponent <exponent>   Degree exponent for power_law [default: 2.5]
+    --lz4                   Compress each output file with LZ4, adding .lz4 to its name
+    --force                 Replace existing output files
+";
+
+fn main() {
+    let args = Docopt::new(USAGE).and_then(|dopt| dopt.parse()).unwrap_or_else(|e| e.exit());
+
+    let output = args.get_str("<output>");
+    let outputs = match args.get_str("<format>") {
+        "vertex" => vec![format!("{}.nodes", output), format!("{}.edges", output)],
+        "hilbert" => vec![format!("{}.upper", output), format!("{}.lower", output)],
+        _ => vec![output.to_owned()],
+    };
+    check_outputs(&outputs, args.get_bool("--force")).or_exit("generate");
+
+    let seed: u64 = args.get_str("--seed").parse().expect("seed not parseable");
+    let mut rng = XorShift::new(seed);
+
+    let mut edges = Vec::new();
+    {
+        let push = |src, dst| edges.push((src, dst));
+        if args.get_bool("rmat") {
+            let scale: u32 = args.get_str("<scale>").parse().expect("scale not parseable");
+            let count: u64 = args.get_str("<edges>").parse().expect("edges not parseable");
+            generators::rmat(scale, count, (0.57, 0.19, 0.19), &mut rng, push);
+        }
+        else if args.get_bool("erdos_renyi") {
+            let nodes: u32 = args.get_str("<nodes>").parse().expect("nodes not parseable");
+            let count: u64 = args.get_str("<edges>").parse().expect("edges not parseable");
+            generators::erdos_renyi(nodes, count, &mut rng, push);
+        }
+        else if args.get_bool("grid") {
+            let width: u32 = args.get_str("<width>").parse().expect("width not parseable");
+            let height: u32 = args.get_str("<height>").parse().expect("height not parseable");
+            generators::grid(width, height, push);
+        }
+        else if args.get_bool("power_law") {
+            let nodes: u32 = args.get_str("<nodes>").parse().expect("nodes not parseable");
+            let count: u64 = args.get_str("<edges>").parse().expect("edges not parseable");
+            let exponent: f64 = args.get_str("--exponent").parse().expect("exponent not parseable");
+            generators::power_law(nodes, count, exponent, &mut rng, push);
+        }
+    }
+
+    // the vertex writer wants edges grouped by source.
+    edges.sort();
+
+    // the compressed format cannot represent duplicate edges, nor the self-loop at vertex zero
+    // (its Hilbert index is zero, and deltas must be positive), so only it loses them.
+    if args.get_str("<format>") == "compressed" {
+        let generated = edges.len();
+        edges.dedup();
+        edges.retain(|&edge| edge != (0, 0));
+        println!("dropped {} duplicate edges and self-loops at zero", generated - edges.len());
+    }
+
+    let lz4 = args.get_bool("--lz4");
+
+    match args.get_str("<format>") {
+        "text" => {
+            let mut writer = AtomicFile::create(output, lz4).or_exit("generate");
+            for &(src, dst) in &edges {
+                writeln!(writer, "{} {}", src, dst).or_exit("generate");
+            }
+            writer.commit().or_exit("generate");
+        },
+        "vertex" => {
+            let mut writer = VertexGraphWriter::new(output, lz4).or_exit("generate");
+            for &(src, dst) in &edges {
+                writer.push(src, dst).or_exit("generate");
+            }
+            writer.finish().or_exit("generate");
+        },
+        "hilbert" => {
+            let mut writer = HilbertGraphWriter::new(output, lz4).or_exit("generate");
+            convert_to_hilbert(&edges, false, |ux, uy, _c, ls| {
+                let (ux, uy) = ((ux as u32) << 16, (uy as u32) << 16);
+                for &(lx, ly) in ls.iter() {
+                    writer.push(ux | lx as u32, uy | ly as u32).or_exit("generate");
+                }
+            });
+            writer.finish().or_exit("generate");
+        },
+        "compressed" => {
+            let mut writer = CompressedGraphWriter::new(output, lz4).or_exit("generate");
+            to_hilbert(&edges, |next| writer.push_index(next).or_exit("generate"));
+            writer.finish().or_exit("generate");
+        },
+        format => { println!("unrecognized format: {:?}", format); },
+    }
+}
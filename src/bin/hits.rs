@@ -0,0 +1,93 @@
+extern crate byteorder;
+extern crate COST;
+
+use std::fs::File;
+
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+use std::io::{BufReader, BufWriter};
+use byteorder::{WriteBytesExt, LittleEndian};
+
+fn main() {
+
+    if std::env::args().len() != 4 && std::env::args().len() != 5 {
+        println!("Usage: hits  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes [<output>]");
+        println!("NOTE: <output>.hubs and <output>.authorities will be overwritten.");
+        return;
+    }
+
+    let mode = std::env::args().nth(1).expect("mode unavailable");
+    let name = std::env::args().nth(2).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let output = std::env::args().nth(4);
+
+    let (hubs, auths) = match mode.as_str() {
+        "vertex" => {
+            hits(&NodesEdgesMemMapper::new(&name).or_exit("hits"), nodes)
+        },
+        "hilbert" => {
+            hits(&UpperLowerMemMapper::new(&name).or_exit("hits"), nodes)
+        },
+        "zorder" => {
+            hits(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("hits"), nodes)
+        },
+        "compressed" => {
+            hits(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("hits"))), nodes)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("hits");
+            hits(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("hits"), nodes)
+        },
+        "varint" => {
+            hits(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("hits"), nodes)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
+    };
+
+    if let Some(output) = output {
+        write_scores(&format!("{}.hubs", output), &hubs);
+        write_scores(&format!("{}.authorities", output), &auths);
+    }
+}
+
+// Kleinberg's hubs and authorities: an authority is pointed at by good hubs, and a hub
+// points at good authorities. Each iteration is one forward scan (hubs -> authorities)
+// and one transposed scan (authorities -> hubs), with both vectors L2-normalized.
+fn hits<G: EdgeMapper>(graph: &G, nodes: u32) -> (Vec<f32>, Vec<f32>) {
+
+    let timer = std::time::Instant::now();
+
+    let mut hub = vec![1f32; nodes as usize];
+    let mut auth = vec![0f32; nodes as usize];
+
+    for _iteration in 0 .. 20 {
+        println!("Iteration {}:\t{:?}", _iteration, timer.elapsed());
+
+        for node in 0 .. nodes { auth[node as usize] = 0f32; }
+        graph.map_edges(|x, y| { unsafe { *auth.get_unchecked_mut(y as usize) += *hub.get_unchecked(x as usize); }});
+        normalize(&mut auth);
+
+        for node in 0 .. nodes { hub[node as usize] = 0f32; }
+        graph.map_edges(|x, y| { unsafe { *hub.get_unchecked_mut(x as usize) += *auth.get_unchecked(y as usize); }});
+        normalize(&mut hub);
+    }
+
+    (hub, auth)
+}
+
+fn normalize(scores: &mut [f32]) {
+    let norm = scores.iter().fold(0f64, |t, &x| t + (x as f64) * (x as f64)).sqrt() as f32;
+    if norm > 0f32 {
+        for score in scores.iter_mut() { *score /= norm; }
+    }
+}
+
+// scores are written as little-endian f32s, indexed by vertex identifier.
+fn write_scores(filename: &str, scores: &[f32]) {
+    let mut writer = BufWriter::new(File::create(filename).or_exit("hits"));
+    for &score in scores {
+        writer.write_f32::<LittleEndian>(score).expect("write error");
+    }
+}
@@ -1,43 +1,86 @@
 extern crate COST;
 
-use std::fs::File;
-
-use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, NodesEdgesMemMapper, UpperLowerMemMapper };
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::typedrw::TypedMemoryMap;
+use COST::report::{Format, OrExit, Report};
 use std::io::BufReader;
 
 fn main() {
 
-    if std::env::args().len() != 4 {
-        println!("Usage: label_propagation  (vertex | hilbert | compressed) <prefix> nodes");
+    let mut args: Vec<String> = std::env::args().collect();
+    let format = Format::extract(&mut args);
+
+    if args.len() != 4 && !(args.len() == 6 && args[4] == "--community") {
+        println!("Usage: label_propagation  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes [--community <max_iterations>] [--format (text | json)]");
         return;
     }
 
-    let mode = std::env::args().nth(1).expect("mode unavailable");
-    let name = std::env::args().nth(2).expect("name unavailable");
-    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let mode = args[1].clone();
+    let name = args[2].clone();
+    let nodes: u32 = args[3].parse().expect("nodes not parseable");
+    let community: Option<u32> = args.get(5).map(|x| x.parse().expect("max_iterations not parseable"));
 
-    match mode.as_str() {
-        "vertex" => {
-            label_propagation(&NodesEdgesMemMapper::new(&name), nodes)
+    let algorithm = if community.is_some() { "label_propagation_community" } else { "label_propagation" };
+    let mut report = Report::new(format, algorithm, &mode, &name);
+    report.set("nodes", nodes);
+
+    match (mode.as_str(), community) {
+        ("vertex", None) => {
+            label_propagation(&NodesEdgesMemMapper::new(&name).or_exit("label_prop"), nodes, &mut report)
+        },
+        ("hilbert", None) => {
+            label_propagation(&UpperLowerMemMapper::new(&name).or_exit("label_prop"), nodes, &mut report)
+        },
+        ("zorder", None) => {
+            label_propagation(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("label_prop"), nodes, &mut report)
+        },
+        ("compressed", None) => {
+            label_propagation(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("label_prop"))), nodes, &mut report)
+        },
+        ("compressed-mmap", None) => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("label_prop");
+            label_propagation(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("label_prop"), nodes, &mut report)
+        },
+        ("varint", None) => {
+            label_propagation(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("label_prop"), nodes, &mut report)
         },
-        "hilbert" => {
-            label_propagation(&UpperLowerMemMapper::new(&name), nodes)
+        ("vertex", Some(iterations)) => {
+            community_propagation(&NodesEdgesMemMapper::new(&name).or_exit("label_prop"), nodes, iterations, &mut report)
         },
-        "compressed" => {
-            label_propagation(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&name).unwrap())), nodes)
+        ("hilbert", Some(iterations)) => {
+            community_propagation(&UpperLowerMemMapper::new(&name).or_exit("label_prop"), nodes, iterations, &mut report)
         },
-        _ => { println!("unrecognized mode: {:?}", mode); },
+        ("zorder", Some(iterations)) => {
+            community_propagation(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("label_prop"), nodes, iterations, &mut report)
+        },
+        ("compressed", Some(iterations)) => {
+            community_propagation(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("label_prop"))), nodes, iterations, &mut report)
+        },
+        ("compressed-mmap", Some(iterations)) => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("label_prop");
+            community_propagation(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("label_prop"), nodes, iterations, &mut report)
+        },
+        ("varint", Some(iterations)) => {
+            community_propagation(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("label_prop"), nodes, iterations, &mut report)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
     }
+
+    report.finish();
 }
 
-fn label_propagation<G: EdgeMapper>(graph: &G, nodes: u32) {
+fn label_propagation<G: EdgeMapper>(graph: &G, nodes: u32, report: &mut Report) {
 
     let mut label: Vec<u32> = (0..nodes).collect();
     let mut old_sum: u64 = label.iter().fold(0, |t,x| t + *x as u64) + 1;
     let mut new_sum: u64 = label.iter().fold(0, |t,x| t + *x as u64);
 
+    let mut edges = 0u64;
     while new_sum < old_sum {
+        edges = 0;
         graph.map_edges(|src, dst| {
+            edges += 1;
             match label[src as usize].cmp(&label[dst as usize]) {
                 std::cmp::Ordering::Less    => label[dst as usize] = label[src as usize],
                 std::cmp::Ordering::Greater => label[src as usize] = label[dst as usize],
@@ -47,10 +90,97 @@ fn label_propagation<G: EdgeMapper>(graph: &G, nodes: u32) {
 
         old_sum = new_sum;
         new_sum = label.iter().fold(0, |t,x| t + *x as u64);
-        println!("iteration");
+        if report.is_text() { println!("iteration"); }
+        report.iteration();
     }
+    report.set("edges", edges);
 
     let mut non_roots = 0u32;
     for i in 0..label.len() { if i as u32 != label[i] { non_roots += 1; }}
-    println!("{} non-roots found", non_roots);
-}
\ No newline at end of file
+    if report.is_text() { println!("{} non-roots found", non_roots); }
+    report.result("non_roots", non_roots);
+}
+
+// Raghavan, Albert, and Kumara's label propagation: each vertex repeatedly adopts the label held by
+// most of its neighbors (ignoring edge direction), and densely connected groups settle on a common
+// label. Vertices are visited in order and see updates immediately; ties keep the current label if
+// it is among the most frequent, and otherwise go to the smallest label, so runs are deterministic.
+fn community_propagation<G: EdgeMapper>(graph: &G, nodes: u32, max_iterations: u32, report: &mut Report) {
+
+    let timer = std::time::Instant::now();
+
+    // most-frequent labels need each vertex's neighbors together, so we build symmetric adjacency lists.
+    let mut offsets = vec![0usize; nodes as usize + 1];
+    let mut edges = 0u64;
+    graph.map_edges(|src, dst| {
+        edges += 1;
+        if src != dst {
+            offsets[src as usize + 1] += 1;
+            offsets[dst as usize + 1] += 1;
+        }
+    });
+    for index in 1 .. offsets.len() { offsets[index] += offsets[index - 1]; }
+    report.set("edges", edges);
+
+    let mut cursor = offsets.clone();
+    let mut neighbors = vec![0u32; offsets[nodes as usize]];
+    graph.map_edges(|src, dst| {
+        if src != dst {
+            neighbors[cursor[src as usize]] = dst;
+            cursor[src as usize] += 1;
+            neighbors[cursor[dst as usize]] = src;
+            cursor[dst as usize] += 1;
+        }
+    });
+
+    let mut label: Vec<u32> = (0..nodes).collect();
+    let mut scratch = Vec::new();
+
+    let mut iteration = 0;
+    let mut changes = 1u64;
+    let mut changed = Vec::new();
+    while changes > 0 && iteration < max_iterations {
+        changes = 0;
+        for node in 0 .. nodes {
+
+            scratch.clear();
+            for &neighbor in &neighbors[offsets[node as usize] .. offsets[node as usize + 1]] {
+                scratch.push(label[neighbor as usize]);
+            }
+            scratch.sort();
+
+            let current = label[node as usize];
+            let mut best = current;
+            let mut best_count = 0;
+            let mut current_count = 0;
+            let mut index = 0;
+            while index < scratch.len() {
+                let mut next = index + 1;
+                while next < scratch.len() && scratch[next] == scratch[index] { next += 1; }
+                if scratch[index] == current { current_count = next - index; }
+                if next - index > best_count {
+                    best = scratch[index];
+                    best_count = next - index;
+                }
+                index = next;
+            }
+
+            if best_count > current_count && best != current {
+                label[node as usize] = best;
+                changes += 1;
+            }
+        }
+
+        iteration += 1;
+        if report.is_text() { println!("{:?}\titeration: {}\tchanged: {}", timer.elapsed(), iteration, changes); }
+        report.iteration();
+        changed.push(changes);
+    }
+
+    let mut seen = vec![false; nodes as usize];
+    let mut communities = 0u32;
+    for &l in &label { if !seen[l as usize] { seen[l as usize] = true; communities += 1; }}
+    if report.is_text() { println!("{} communities found", communities); }
+    report.result("communities", communities);
+    report.result("changed", changed);
+}
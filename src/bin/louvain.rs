@@ -0,0 +1,271 @@
+extern crate byteorder;
+extern crate COST;
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use COST::graph_iterator::NodesEdgesMemMapper;
+use COST::report::OrExit;
+use byteorder::{WriteBytesExt, LittleEndian};
+
+fn main() {
+
+    if std::env::args().len() != 3 && std::env::args().len() != 4 {
+        println!("Usage: louvain <prefix> nodes [<output>]");
+        println!("NOTE: <output> will be overwritten.");
+        return;
+    }
+
+    let name = std::env::args().nth(1).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(2).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let output = std::env::args().nth(3);
+
+    let community = louvain(&NodesEdgesMemMapper::new(&name).or_exit("louvain"), nodes);
+
+    if let Some(output) = output {
+        // assignments are written as little-endian u32s, indexed by vertex identifier.
+        let mut writer = BufWriter::new(File::create(output).or_exit("louvain"));
+        for &label in &community {
+            writer.write_u32::<LittleEndian>(label).expect("write error");
+        }
+    }
+}
+
+// Blondel et al.'s Louvain method: repeatedly move single vertices to the neighboring community
+// with the largest modularity gain until nothing moves, then collapse each community to a vertex
+// and repeat on the smaller graph. Edge directions are ignored.
+fn louvain(graph: &NodesEdgesMemMapper, nodes: u32) -> Vec<u32> {
+
+    let timer = std::time::Instant::now();
+
+    let mut level = Graph::from_vertex(graph, nodes);
+    let mut community: Vec<u32> = (0..nodes).collect();
+
+    let mut depth = 0;
+    loop {
+        let (assignment, count) = level.one_level();
+        let modularity = level.modularity(&assignment);
+
+        println!("{:?}\tlevel: {}\tcommunities: {}\tmodularity: {:.6}", timer.elapsed(), depth, count, modularity);
+
+        for label in community.iter_mut() {
+            *label = assignment[*label as usize];
+        }
+
+        if count == level.nodes() { break; }
+
+        level = level.aggregate(&assignment, count);
+        depth += 1;
+    }
+
+    community
+}
+
+// the smallest modularity gain, in units of edge weight, worth moving a vertex for.
+const MIN_GAIN: f64 = 1e-9;
+// the most passes over the vertices within one level.
+const MAX_PASSES: usize = 100;
+
+// an undirected weighted graph, with each edge present in the adjacency lists of both endpoints.
+// a self-loop appears once, with twice the weight of the edge, so that degrees sum correctly.
+struct Graph {
+    offsets:    Vec<usize>,
+    targets:    Vec<u32>,
+    weights:    Vec<f64>,
+}
+
+impl Graph {
+
+    fn from_vertex(graph: &NodesEdgesMemMapper, nodes: u32) -> Graph {
+
+        let mut offsets = vec![0usize; nodes as usize + 1];
+        graph.map_nodes(|node, edges| {
+            for &edge in edges {
+                offsets[node as usize + 1] += 1;
+                if edge != node { offsets[edge as usize + 1] += 1; }
+            }
+        });
+        for index in 1 .. offsets.len() { offsets[index] += offsets[index - 1]; }
+
+        let mut cursor = offsets.clone();
+        let mut targets = vec![0u32; offsets[nodes as usize]];
+        let mut weights = vec![0f64; offsets[nodes as usize]];
+        graph.map_nodes(|node, edges| {
+            for &edge in edges {
+                if edge != node {
+                    targets[cursor[node as usize]] = edge;
+                    weights[cursor[node as usize]] = 1.0;
+                    cursor[node as usize] += 1;
+                    targets[cursor[edge as usize]] = node;
+                    weights[cursor[edge as usize]] = 1.0;
+                    cursor[edge as usize] += 1;
+                }
+                else {
+                    targets[cursor[node as usize]] = node;
+                    weights[cursor[node as usize]] = 2.0;
+                    cursor[node as usize] += 1;
+                }
+            }
+        });
+
+        Graph { offsets, targets, weights }
+    }
+
+    fn nodes(&self) -> u32 { (self.offsets.len() - 1) as u32 }
+
+    fn neighbors(&self, node: u32) -> (&[u32], &[f64]) {
+        let lower = self.offsets[node as usize];
+        let upper = self.offsets[node as usize + 1];
+        (&self.targets[lower .. upper], &self.weights[lower .. upper])
+    }
+
+    fn degree(&self, node: u32) -> f64 {
+        self.neighbors(node).1.iter().fold(0f64, |t, &w| t + w)
+    }
+
+    // moves vertices between communities until no move improves modularity, and returns
+    // the resulting assignment renumbered densely from zero, along with the number of communities.
+    fn one_level(&self) -> (Vec<u32>, u32) {
+
+        let nodes = self.nodes();
+        let degree: Vec<f64> = (0 .. nodes).map(|node| self.degree(node)).collect();
+        let total = degree.iter().fold(0f64, |t, &d| t + d);
+
+        let mut assignment: Vec<u32> = (0 .. nodes).collect();
+        let mut community_degree = degree.clone();
+
+        // scratch space for accumulating edge weight from a vertex to each neighboring community.
+        let mut weight_to = vec![-1f64; nodes as usize];
+        let mut touched = Vec::new();
+
+        // a move must beat staying put by at least MIN_GAIN, so rounding noise cannot shuffle vertices
+        // between equally good communities forever; MAX_PASSES bounds the work regardless.
+        let mut moves = 1;
+        let mut passes = 0;
+        while moves > 0 && total > 0.0 && passes < MAX_PASSES {
+            moves = 0;
+            passes += 1;
+            for node in 0 .. nodes {
+
+                let current = assignment[node as usize];
+
+                let (targets, weights) = self.neighbors(node);
+                weight_to[current as usize] = 0.0;
+                touched.push(current);
+                for (&target, &weight) in targets.iter().zip(weights.iter()) {
+                    if target != node {
+                        let community = assignment[target as usize];
+                        if weight_to[community as usize] < 0.0 {
+                            weight_to[community as usize] = 0.0;
+                            touched.push(community);
+                        }
+                        weight_to[community as usize] += weight;
+                    }
+                }
+
+                // remove node from its community, then re-insert it wherever the gain is largest,
+                // preferring its current community and otherwise the first community encountered.
+                community_degree[current as usize] -= degree[node as usize];
+
+                let gain = |community: u32| {
+                    weight_to[community as usize] - community_degree[community as usize] * degree[node as usize] / total
+                };
+
+                let mut best = current;
+                let mut best_gain = gain(current) + MIN_GAIN;
+                for &community in &touched {
+                    let candidate = gain(community);
+                    if candidate > best_gain {
+                        best = community;
+                        best_gain = candidate;
+                    }
+                }
+
+                community_degree[best as usize] += degree[node as usize];
+                assignment[node as usize] = best;
+                if best != current { moves += 1; }
+
+                for &community in &touched { weight_to[community as usize] = -1.0; }
+                touched.clear();
+            }
+        }
+
+        let mut renumber = vec![u32::MAX; nodes as usize];
+        let mut count = 0;
+        for label in assignment.iter_mut() {
+            if renumber[*label as usize] == u32::MAX {
+                renumber[*label as usize] = count;
+                count += 1;
+            }
+            *label = renumber[*label as usize];
+        }
+
+        (assignment, count)
+    }
+
+    // Newman's modularity: the fraction of edge weight within communities, less the fraction
+    // expected if edges were placed at random preserving degrees.
+    fn modularity(&self, assignment: &[u32]) -> f64 {
+        let communities = assignment.iter().map(|&c| c as usize + 1).max().unwrap_or(0);
+        let mut inside = vec![0f64; communities];
+        let mut incident = vec![0f64; communities];
+        let mut total = 0f64;
+
+        for node in 0 .. self.nodes() {
+            let community = assignment[node as usize] as usize;
+            let (targets, weights) = self.neighbors(node);
+            for (&target, &weight) in targets.iter().zip(weights.iter()) {
+                if assignment[target as usize] as usize == community {
+                    inside[community] += weight;
+                }
+                incident[community] += weight;
+                total += weight;
+            }
+        }
+
+        if total == 0.0 { return 0.0; }
+        inside.iter().zip(incident.iter()).fold(0f64, |q, (&i, &d)| q + i / total - (d / total) * (d / total))
+    }
+
+    // collapses each community into a single vertex, with self-loops holding internal weight.
+    fn aggregate(&self, assignment: &[u32], count: u32) -> Graph {
+
+        let mut members = vec![Vec::new(); count as usize];
+        for node in 0 .. self.nodes() {
+            members[assignment[node as usize] as usize].push(node);
+        }
+
+        let mut offsets = vec![0usize];
+        let mut targets = Vec::new();
+        let mut weights = Vec::new();
+
+        let mut weight_to = vec![-1f64; count as usize];
+        let mut touched = Vec::new();
+
+        for community in 0 .. count {
+            for &node in &members[community as usize] {
+                let (node_targets, node_weights) = self.neighbors(node);
+                for (&target, &weight) in node_targets.iter().zip(node_weights.iter()) {
+                    let other = assignment[target as usize];
+                    if weight_to[other as usize] < 0.0 {
+                        weight_to[other as usize] = 0.0;
+                        touched.push(other);
+                    }
+                    weight_to[other as usize] += weight;
+                }
+            }
+
+            touched.sort();
+            for &other in &touched {
+                targets.push(other);
+                weights.push(weight_to[other as usize]);
+                weight_to[other as usize] = -1.0;
+            }
+            touched.clear();
+
+            offsets.push(targets.len());
+        }
+
+        Graph { offsets, targets, weights }
+    }
+}
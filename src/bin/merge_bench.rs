@@ -0,0 +1,46 @@
+extern crate COST;
+
+use std::time::Instant;
+
+use COST::hilbert_curve::{encode, merge, merge_parallel, Decoder};
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+
+fn main() {
+
+    if std::env::args().len() < 3 {
+        println!("Usage: merge_bench <threads> <source>...");
+        println!("NOTE: times a serial merge of the delta-compressed <source> files against merge_parallel with <threads> threads.");
+        return;
+    }
+
+    let threads: usize = std::env::args().nth(1).expect("threads unavailable").parse().expect("threads not parseable");
+    let names: Vec<String> = std::env::args().skip(2).collect();
+
+    // both merges read the same mapped sources and write to memory, so that only merging is timed.
+    let maps: Vec<TypedMemoryMap<u8>> = names.iter().map(|name| TypedMemoryMap::new(name.clone()).or_exit("merge_bench")).collect();
+    let sources: Vec<&[u8]> = maps.iter().map(|map| &map[..]).collect();
+    println!("{} sources, {} bytes", sources.len(), sources.iter().map(|source| source.len()).sum::<usize>());
+
+    let timer = Instant::now();
+    let mut serial = Vec::new();
+    let mut prev = 0u64;
+    merge(sources.iter().map(|&source| Decoder::new(source)).collect(), |next| {
+        if prev < next {
+            encode(&mut serial, next - prev);
+            prev = next;
+        }
+    });
+    let serial_time = timer.elapsed().as_secs_f64();
+
+    let timer = Instant::now();
+    let mut parallel = Vec::new();
+    merge_parallel(&sources, threads, &mut parallel).or_exit("merge_bench");
+    let parallel_time = timer.elapsed().as_secs_f64();
+
+    assert!(serial == parallel, "serial and parallel merges disagree");
+
+    println!("serial merge:\t{:.4}s", serial_time);
+    println!("parallel merge ({} threads):\t{:.4}s", threads, parallel_time);
+    println!("speedup:\t{:.2}x", serial_time / parallel_time);
+}
@@ -0,0 +1,104 @@
+extern crate COST;
+
+use COST::compression;
+use COST::generators::splitmix64;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::report::OrExit;
+use COST::typedrw::TypedMemoryMap;
+use std::io::BufReader;
+
+const UNDECIDED: u8 = 0;
+const INCLUDED: u8 = 1;
+const EXCLUDED: u8 = 2;
+
+fn main() {
+
+    if std::env::args().len() != 4 {
+        println!("Usage: mis  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes");
+        return;
+    }
+
+    let mode = std::env::args().nth(1).expect("mode unavailable");
+    let name = std::env::args().nth(2).expect("name unavailable");
+    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+
+    match mode.as_str() {
+        "vertex" => {
+            mis(&NodesEdgesMemMapper::new(&name).or_exit("mis"), nodes)
+        },
+        "hilbert" => {
+            mis(&UpperLowerMemMapper::new(&name).or_exit("mis"), nodes)
+        },
+        "zorder" => {
+            mis(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("mis"), nodes)
+        },
+        "compressed" => {
+            mis(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("mis"))), nodes)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("mis");
+            mis(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("mis"), nodes)
+        },
+        "varint" => {
+            mis(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("mis"), nodes)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); },
+    }
+}
+
+// Luby's maximal independent set: each round every undecided vertex draws a random priority, those
+// beating all undecided neighbors join the set, and their neighbors leave. Each round is two scans.
+// Edge directions and self-loops are ignored.
+fn mis<G: EdgeMapper>(graph: &G, nodes: u32) {
+
+    let timer = std::time::Instant::now();
+
+    let mut state = vec![UNDECIDED; nodes as usize];
+    let mut beaten = vec![false; nodes as usize];
+
+    let mut round = 0u64;
+    let mut undecided = nodes as u64;
+    while undecided > 0 {
+
+        let priority = |node: u32| (splitmix64((round << 32) + node as u64), node);
+
+        graph.map_edges(|x, y| {
+            if x != y && state[x as usize] == UNDECIDED && state[y as usize] == UNDECIDED {
+                if priority(x) < priority(y) { beaten[x as usize] = true; }
+                else                         { beaten[y as usize] = true; }
+            }
+        });
+
+        for node in 0 .. nodes as usize {
+            if state[node] == UNDECIDED && !beaten[node] { state[node] = INCLUDED; }
+            beaten[node] = false;
+        }
+
+        graph.map_edges(|x, y| {
+            if state[x as usize] == INCLUDED && state[y as usize] == UNDECIDED { state[y as usize] = EXCLUDED; }
+            if state[y as usize] == INCLUDED && state[x as usize] == UNDECIDED { state[x as usize] = EXCLUDED; }
+        });
+
+        undecided = state.iter().filter(|&&s| s == UNDECIDED).count() as u64;
+        round += 1;
+        println!("{:?}\tround: {}\tundecided: {}", timer.elapsed(), round, undecided);
+    }
+
+    // verify independence (no edge within the set) and maximality (every other vertex has a neighbor in it).
+    let mut violations = 0u64;
+    let mut covered = vec![false; nodes as usize];
+    graph.map_edges(|x, y| {
+        if x != y {
+            if state[x as usize] == INCLUDED && state[y as usize] == INCLUDED { violations += 1; }
+            if state[x as usize] == INCLUDED { covered[y as usize] = true; }
+            if state[y as usize] == INCLUDED { covered[x as usize] = true; }
+        }
+    });
+
+    let size = state.iter().filter(|&&s| s == INCLUDED).count();
+    let uncovered = (0 .. nodes as usize).filter(|&node| state[node] == EXCLUDED && !covered[node]).count();
+
+    println!("{} vertices in independent set", size);
+    println!("{} edges violated", violations);
+    println!("{} excluded vertices without a neighbor in the set", uncovered);
+}
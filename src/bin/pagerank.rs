@@ -1,36 +1,55 @@
 extern crate COST;
 
-use std::fs::File;
-
-use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, NodesEdgesMemMapper, UpperLowerMemMapper };
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::typedrw::TypedMemoryMap;
+use COST::report::{Format, OrExit, Report};
 use std::io::BufReader;
 
 fn main() {
 
-    if std::env::args().len() != 4 {
-        println!("Usage: pagerank  (vertex | hilbert | compressed) <prefix> nodes");
+    let mut args: Vec<String> = std::env::args().collect();
+    let format = Format::extract(&mut args);
+
+    if args.len() != 4 {
+        println!("Usage: pagerank  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes [--format (text | json)]");
         return;
     }
 
-    let mode = std::env::args().nth(1).expect("mode unavailable");
-    let name = std::env::args().nth(2).expect("name unavailable");
-    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let mode = args[1].clone();
+    let name = args[2].clone();
+    let nodes: u32 = args[3].parse().expect("nodes not parseable");
+
+    let mut report = Report::new(format, "pagerank", &mode, &name);
+    report.set("nodes", nodes);
 
     match mode.as_str() {
         "vertex" => {
-            pagerank(&NodesEdgesMemMapper::new(&name), nodes, 0.85f32)
+            pagerank(&NodesEdgesMemMapper::new(&name).or_exit("pagerank"), nodes, 0.85f32, &mut report)
         },
         "hilbert" => {
-            pagerank(&UpperLowerMemMapper::new(&name), nodes, 0.85f32)
+            pagerank(&UpperLowerMemMapper::new(&name).or_exit("pagerank"), nodes, 0.85f32, &mut report)
+        },
+        "zorder" => {
+            pagerank(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("pagerank"), nodes, 0.85f32, &mut report)
         },
         "compressed" => {
-            pagerank(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&name).unwrap())), nodes, 0.85f32)
+            pagerank(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("pagerank"))), nodes, 0.85f32, &mut report)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("pagerank");
+            pagerank(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("pagerank"), nodes, 0.85f32, &mut report)
         },
-        _ => { println!("unrecognized mode: {:?}", mode); },
+        "varint" => {
+            pagerank(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("pagerank"), nodes, 0.85f32, &mut report)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
     }
+
+    report.finish();
 }
 
-fn pagerank<G: EdgeMapper>(graph: &G, nodes: u32, alpha: f32) {
+fn pagerank<G: EdgeMapper>(graph: &G, nodes: u32, alpha: f32, report: &mut Report) {
 
     let timer = std::time::Instant::now();
 
@@ -38,10 +57,12 @@ fn pagerank<G: EdgeMapper>(graph: &G, nodes: u32, alpha: f32) {
     let mut dst = vec![0f32; nodes as usize];
     let mut deg = vec![0f32; nodes as usize];
 
-    graph.map_edges(|x, _| { deg[x as usize] += 1f32 });
+    let mut edges = 0u64;
+    graph.map_edges(|x, _| { deg[x as usize] += 1f32; edges += 1; });
+    report.set("edges", edges);
 
     for _iteration in 0 .. 20 {
-        println!("Iteration {}:\t{:?}", _iteration, timer.elapsed());
+        if report.is_text() { println!("Iteration {}:\t{:?}", _iteration, timer.elapsed()); }
         for node in 0 .. nodes {
             src[node as usize] = alpha * dst[node as usize] / deg[node as usize];
             dst[node as usize] = 1f32 - alpha;
@@ -51,5 +72,13 @@ fn pagerank<G: EdgeMapper>(graph: &G, nodes: u32, alpha: f32) {
 
         // UNSAFE:
         graph.map_edges(|x, y| { unsafe { *dst.get_unchecked_mut(y as usize) += *src.get_unchecked(x as usize); }});
+        report.iteration();
     }
-}
\ No newline at end of file
+
+    let mut max_vertex = 0;
+    for node in 0 .. nodes {
+        if dst[node as usize] > dst[max_vertex as usize] { max_vertex = node; }
+    }
+    report.result("max_vertex", max_vertex);
+    report.result("max_rank", if nodes > 0 { dst[max_vertex as usize] } else { 0f32 });
+}
@@ -1,46 +1,242 @@
 extern crate COST;
 
-use std::fs::File;
-
-use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, NodesEdgesMemMapper, UpperLowerMemMapper };
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::hilbert_curve::{DEFAULT_LOW_BITS, split_bytes};
+use COST::typedrw::TypedMemoryMap;
+use COST::report::{Format, OrExit, Report, Value};
 use std::io::BufReader;
 
 fn main() {
 
-    if std::env::args().len() != 3 {
-        println!("Usage: stats  (vertex | hilbert | compressed) <prefix>");
+    let mut args: Vec<String> = std::env::args().collect();
+    let format = Format::extract(&mut args);
+    let splits = args.iter().any(|arg| arg == "--splits");
+    args.retain(|arg| arg != "--splits");
+
+    if args.len() != 3 {
+        println!("Usage: stats  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> [--splits] [--format (text | json)]");
+        println!("NOTE: --splits sizes the Hilbert layout for each split of `to_hilbert --split` and suggests the smallest;");
+        println!("      it needs edges in Hilbert order, from the hilbert, compressed, or compressed-mmap modes.");
         return;
     }
 
-    let mode = std::env::args().nth(1).expect("mode unavailable");
-    let name = std::env::args().nth(2).expect("name unavailable");
+    let mode = args[1].clone();
+    let name = args[2].clone();
+
+    let mut report = Report::new(format, "stats", &mode, &name);
 
-    match mode.as_str() {
+    let stats = match mode.as_str() {
         "vertex" => {
-            stats(&NodesEdgesMemMapper::new(&name));
+            stats(&NodesEdgesMemMapper::new(&name).or_exit("stats"), splits)
         },
         "hilbert" => {
-            stats(&UpperLowerMemMapper::new(&name));
+            stats(&UpperLowerMemMapper::new(&name).or_exit("stats"), splits)
+        },
+        "zorder" => {
+            stats(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("stats"), splits)
         },
         "compressed" => {
-            stats(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&name).unwrap())));
+            stats(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("stats"))), splits)
         },
-        _ => { println!("unrecognized mode: {:?}", mode); },
-    }
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("stats");
+            stats(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("stats"), splits)
+        },
+        "varint" => {
+            stats(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("stats"), splits)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
+    };
+
+    report.iteration();
+    if report.is_text() { stats.print_text(); }
+    stats.record(&mut report);
+    report.finish();
 }
 
-fn stats<G: EdgeMapper>(graph: &G) -> u32 {
+struct Stats {
+    max_x:      u32,
+    max_y:      u32,
+    edges:      u64,
+    self_loops: u64,
+    duplicates: u64,
+    out_degree: Vec<u32>,
+    in_degree:  Vec<u32>,
+    splits:     Option<Vec<(u32, u64)>>,
+}
+
+// Duplicate edges are only detected within runs of consecutive edges sharing a source. This covers
+// the vertex layout, where each source's edges are contiguous, and the Hilbert and compressed layouts,
+// where duplicates are adjacent, without having to hold the whole edge set in memory.
+fn stats<G: EdgeMapper>(graph: &G, splits: bool) -> Stats {
+    // sizing splits needs edges in Hilbert order, so it goes first, to fail before the longer scan.
+    let splits = if splits { Some(split_bytes(graph).or_exit("stats")) } else { None };
+
     let mut max_x = 0;
     let mut max_y = 0;
     let mut edges = 0u64;
+    let mut self_loops = 0u64;
+    let mut duplicates = 0u64;
+    let mut out_degree = Vec::new();
+    let mut in_degree = Vec::new();
+
+    let mut run_source = None;
+    let mut run = Vec::new();
+
     graph.map_edges(|x, y| {
         if max_x < x { max_x = x; }
         if max_y < y { max_y = y; }
         edges += 1;
+
+        if x == y { self_loops += 1; }
+
+        if out_degree.len() <= x as usize { out_degree.resize(x as usize + 1, 0u32); }
+        if in_degree.len() <= y as usize { in_degree.resize(y as usize + 1, 0u32); }
+        out_degree[x as usize] += 1;
+        in_degree[y as usize] += 1;
+
+        if run_source != Some(x) {
+            duplicates += count_duplicates(&mut run);
+            run_source = Some(x);
+        }
+        run.push(y);
     });
+    duplicates += count_duplicates(&mut run);
+
+    // both degree vectors cover all vertices, isolated or not.
+    let vertices = if edges > 0 { std::cmp::max(max_x, max_y) as usize + 1 } else { 0 };
+    out_degree.resize(vertices, 0);
+    in_degree.resize(vertices, 0);
+
+    Stats {
+        max_x,
+        max_y,
+        edges,
+        self_loops,
+        duplicates,
+        out_degree,
+        in_degree,
+        splits,
+    }
+}
+
+fn count_duplicates(run: &mut Vec<u32>) -> u64 {
+    run.sort();
+    let mut duplicates = 0;
+    for index in 1 .. run.len() {
+        if run[index] == run[index - 1] { duplicates += 1; }
+    }
+    run.clear();
+    duplicates
+}
+
+impl Stats {
+
+    fn vertices(&self) -> usize { self.out_degree.len() }
+
+    fn non_isolated(&self) -> usize {
+        self.out_degree.iter().zip(self.in_degree.iter()).filter(|&(&o, &i)| o > 0 || i > 0).count()
+    }
 
-    println!("max x: {}", max_x);
-    println!("max y: {}", max_y);
-    println!("edges: {}", edges);
-    std::cmp::max(max_x, max_y) + 1
+    // edges relative to the number of possible directed edges between distinct vertices.
+    fn density(&self) -> f64 {
+        let vertices = self.vertices() as f64;
+        if vertices > 1.0 { self.edges as f64 / (vertices * (vertices - 1.0)) } else { 0.0 }
+    }
+
+    fn print_text(&self) {
+        println!("max x: {}", self.max_x);
+        println!("max y: {}", self.max_y);
+        println!("edges: {}", self.edges);
+        println!("vertices: {}", self.vertices());
+        println!("non-isolated vertices: {}", self.non_isolated());
+        println!("self-loops: {}", self.self_loops);
+        println!("duplicate edges: {}", self.duplicates);
+        println!("density: {:e}", self.density());
+        for &(name, degrees) in &[("out", &self.out_degree), ("in", &self.in_degree)] {
+            let summary = Degrees::new(degrees);
+            println!("max {}-degree: {} (vertex {})", name, summary.max, summary.argmax);
+            print!("{}-degree percentiles:", name);
+            for &(percentile, degree) in &summary.percentiles { print!(" p{}: {}", percentile, degree); }
+            println!();
+            println!("{}-degree histogram:", name);
+            for &(lower, count) in &summary.histogram { println!("\t{}\t{}", lower, count); }
+        }
+        if let Some(ref splits) = self.splits {
+            println!("hilbert layout bytes by split:");
+            for &(low_bits, bytes) in splits { println!("\t{}\t{}", low_bits, bytes); }
+            println!("suggested split: {}", suggested_split(splits));
+        }
+    }
+
+    fn record(&self, report: &mut Report) {
+        report.set("nodes", self.vertices());
+        report.set("edges", self.edges);
+        report.result("max_x", self.max_x);
+        report.result("max_y", self.max_y);
+        report.result("non_isolated_vertices", self.non_isolated());
+        report.result("self_loops", self.self_loops);
+        report.result("duplicate_edges", self.duplicates);
+        report.result("density", self.density());
+        for &(name, degrees) in &[("out_degree", &self.out_degree), ("in_degree", &self.in_degree)] {
+            let summary = Degrees::new(degrees);
+            let percentiles = summary.percentiles.iter().map(|&(p, d)| (format!("p{}", p), d.into())).collect();
+            let histogram: Vec<Vec<u64>> = summary.histogram.iter().map(|&(lower, count)| vec![lower as u64, count]).collect();
+            report.result(name, Value::Object(vec![
+                ("max".to_owned(), summary.max.into()),
+                ("argmax".to_owned(), summary.argmax.into()),
+                ("percentiles".to_owned(), Value::Object(percentiles)),
+                ("histogram".to_owned(), histogram.into()),
+            ]));
+        }
+        if let Some(ref splits) = self.splits {
+            let bytes = splits.iter().map(|&(low_bits, bytes)| (low_bits.to_string(), bytes.into())).collect();
+            report.result("split_bytes", Value::Object(bytes));
+            report.result("suggested_split", suggested_split(splits));
+        }
+    }
+}
+
+// the split with the smallest layout, preferring the default split and then smaller blocks on ties.
+fn suggested_split(splits: &[(u32, u64)]) -> u32 {
+    splits.iter().min_by_key(|&&(low_bits, bytes)| (bytes, low_bits != DEFAULT_LOW_BITS, low_bits)).map(|&(low_bits, _)| low_bits).unwrap_or(DEFAULT_LOW_BITS)
+}
+
+// summarizes a degree distribution; histogram buckets hold degree zero, then [1, 2), [2, 4), [4, 8), ...
+// and are reported by their lower bound.
+struct Degrees {
+    max:            u32,
+    argmax:         usize,
+    percentiles:    Vec<(&'static str, u32)>,
+    histogram:      Vec<(u32, u64)>,
+}
+
+impl Degrees {
+    fn new(degrees: &[u32]) -> Degrees {
+        let mut max = 0;
+        let mut argmax = 0;
+        let mut buckets = vec![0u64; 33];
+        for (vertex, &degree) in degrees.iter().enumerate() {
+            if degree > max { max = degree; argmax = vertex; }
+            buckets[(32 - degree.leading_zeros()) as usize] += 1;
+        }
+
+        let mut histogram = Vec::new();
+        for (bucket, &count) in buckets.iter().enumerate() {
+            if count > 0 { histogram.push((if bucket == 0 { 0 } else { 1 << (bucket - 1) }, count)); }
+        }
+
+        let mut sorted = degrees.to_vec();
+        sorted.sort();
+        let mut percentiles = Vec::new();
+        for &(name, fraction) in &[("50", 0.5), ("90", 0.9), ("99", 0.99), ("99.9", 0.999)] {
+            if !sorted.is_empty() {
+                let index = std::cmp::min(((sorted.len() as f64) * fraction) as usize, sorted.len() - 1);
+                percentiles.push((name, sorted[index]));
+            }
+        }
+
+        Degrees { max, argmax, percentiles, histogram }
+    }
 }
@@ -1,33 +1,60 @@
-extern crate byteorder;
 extern crate COST;
 
-use std::fs::File;
-use std::io::BufWriter;
-use byteorder::{WriteBytesExt, LittleEndian};
 use COST::graph_iterator::NodesEdgesMemMapper;
+use COST::hilbert_curve::DEFAULT_LOW_BITS;
+use COST::report::OrExit;
+use COST::writers::{check_outputs, HilbertGraphWriter};
 
 fn main() {
 
-    if std::env::args().len() != 2 && std::env::args().len() != 3 {
-        println!("Usage: to_hilbert <prefix> [--dense]");
-        println!("NOTE: <prefix>.upper and <prefix>.lower will be overwritten.");
+    let mut args: Vec<String> = std::env::args().collect();
+    let dense = args.iter().any(|arg| arg == "--dense");
+    let lz4 = args.iter().any(|arg| arg == "--lz4");
+    let force = args.iter().any(|arg| arg == "--force");
+    args.retain(|arg| arg != "--dense" && arg != "--lz4" && arg != "--force");
+
+    let mut low_bits = DEFAULT_LOW_BITS;
+    if let Some(position) = args.iter().position(|arg| arg == "--split") {
+        low_bits = args.get(position + 1).and_then(|bits| bits.parse().ok()).filter(|bits| (8 ..= 24).contains(bits)).unwrap_or_else(|| {
+            println!("--split expects a number of low bits from 8 to 24");
+            std::process::exit(1);
+        });
+        args.drain(position .. position + 2);
+    }
+
+    if args.len() != 2 {
+        println!("Usage: to_hilbert <prefix> [--dense] [--lz4] [--split <low_bits>] [--force]");
+        println!("NOTE: writes <prefix>.upper and <prefix>.lower, replacing existing files only with --force.");
+        println!("NOTE: --lz4 compresses the outputs, adding .lz4 to their names.");
+        println!("NOTE: --split sets the low bits of each coordinate held in <prefix>.lower (8 to 24, default 16),");
+        println!("      recording other splits in a header of <prefix>.upper; `stats --splits` suggests one.");
         return;
     }
 
-    let prefix = std::env::args().nth(1).expect("name unavailable");
-    let dense = std::env::args().nth(2) == Some("--dense".to_string());
-
-    let graph = NodesEdgesMemMapper::new(&prefix);
-    let mut u_writer = BufWriter::new(File::create(format!("{}.upper", prefix)).unwrap());
-    let mut l_writer = BufWriter::new(File::create(format!("{}.lower", prefix)).unwrap());
-
-    COST::hilbert_curve::convert_to_hilbert(&graph, dense, |ux, uy, c, ls| {
-        u_writer.write_u16::<LittleEndian>(ux).unwrap();
-        u_writer.write_u16::<LittleEndian>(uy).unwrap();
-        u_writer.write_u32::<LittleEndian>(c).unwrap();
-        for &(lx, ly) in ls.iter(){
-            l_writer.write_u16::<LittleEndian>(lx).unwrap();
-            l_writer.write_u16::<LittleEndian>(ly).unwrap();
-        }
-    });
-}
\ No newline at end of file
+    let prefix = args[1].clone();
+
+    check_outputs(&[format!("{}.upper", prefix), format!("{}.lower", prefix)], force).or_exit("to_hilbert");
+
+    let graph = NodesEdgesMemMapper::new(&prefix).or_exit("to_hilbert");
+    let mut writer = HilbertGraphWriter::with_split(&prefix, low_bits, lz4).or_exit("to_hilbert");
+    let mut result = Ok(());
+
+    if low_bits == DEFAULT_LOW_BITS {
+        COST::hilbert_curve::convert_to_hilbert(&graph, dense, |ux, uy, _c, ls| {
+            let (ux, uy) = ((ux as u32) << 16, (uy as u32) << 16);
+            for &(lx, ly) in ls.iter() {
+                if result.is_ok() { result = writer.push(ux | lx as u32, uy | ly as u32); }
+            }
+        });
+    }
+    else {
+        COST::hilbert_curve::convert_to_hilbert_split(&graph, dense, low_bits, |ux, uy, _c, ls| {
+            let (ux, uy) = (ux << low_bits, uy << low_bits);
+            for &(lx, ly) in ls.iter() {
+                if result.is_ok() { result = writer.push(ux | lx, uy | ly); }
+            }
+        });
+    }
+
+    result.and_then(|_| writer.finish()).or_exit("to_hilbert");
+}
@@ -1,46 +1,39 @@
-extern crate byteorder;
 extern crate COST;
 
-use std::io::{BufReader, BufWriter};
-use std::fs::File;
+use std::io::BufReader;
+use COST::compression;
 use COST::graph_iterator::{EdgeMapper, ReaderMapper};
-use byteorder::{WriteBytesExt, LittleEndian};
+use COST::report::OrExit;
+use COST::writers::{check_outputs, VertexGraphWriter};
 
 fn main() {
 
-    if std::env::args().len() != 3 {
-        println!("Usage: to_vertex <source> <prefix>");
-        println!("NOTE: <prefix>.nodes and <prefix>.edges will be overwritten.");
+    let mut args: Vec<String> = std::env::args().collect();
+    let lz4 = args.iter().any(|arg| arg == "--lz4");
+    let force = args.iter().any(|arg| arg == "--force");
+    args.retain(|arg| arg != "--lz4" && arg != "--force");
+
+    if args.len() != 3 {
+        println!("Usage: to_vertex <source> <prefix> [--lz4] [--force]");
+        println!("NOTE: writes <prefix>.nodes and <prefix>.edges, replacing existing files only with --force.");
+        println!("NOTE: <source> may list edges in any order; a new node record starts whenever the source changes.");
+        println!("NOTE: <source> is LZ4-compressed if its name ends in .lz4; --lz4 compresses the outputs, adding .lz4");
+        println!("      to their names.");
         return;
     }
 
-    let source = std::env::args().nth(1).expect("source unavailable"); let source = &source;
-    let target = std::env::args().nth(2).expect("prefix unavailable"); let target = &target;
+    let source = &args[1];
+    let target = &args[2];
 
-    let reader_mapper = ReaderMapper { reader: || BufReader::new(File::open(source).unwrap()) };
+    check_outputs(&[format!("{}.nodes", target), format!("{}.edges", target)], force).or_exit("to_vertex");
 
-    let mut edge_writer = BufWriter::new(File::create(format!("{}.edges", target)).unwrap());
-    let mut node_writer = BufWriter::new(File::create(format!("{}.nodes", target)).unwrap());
-
-    let mut cnt = 0;
-    let mut src = 0;
+    let reader_mapper = ReaderMapper { reader: || BufReader::new(compression::open(source).or_exit("to_vertex")) };
 
+    let mut writer = VertexGraphWriter::unsorted(target, lz4).or_exit("to_vertex");
+    let mut result = Ok(());
     reader_mapper.map_edges(|x, y| {
-        if x != src {
-            if cnt > 0 {
-                node_writer.write_u32::<LittleEndian>(src).ok().expect("write error");
-                node_writer.write_u32::<LittleEndian>(cnt).ok().expect("write error");
-                cnt = 0;
-            }
-            src = x;
-        }
-
-        edge_writer.write_u32::<LittleEndian>(y).ok().expect("write error");
-        cnt += 1;
+        if result.is_ok() { result = writer.push(x, y); }
     });
 
-    if cnt > 0 {
-        node_writer.write_u32::<LittleEndian>(src).ok().expect("write error");
-        node_writer.write_u32::<LittleEndian>(cnt).ok().expect("write error");
-    }
-}
\ No newline at end of file
+    result.and_then(|_| writer.finish()).or_exit("to_vertex");
+}
@@ -0,0 +1,53 @@
+extern crate byteorder;
+extern crate COST;
+
+use std::io;
+
+use byteorder::{WriteBytesExt, LittleEndian};
+use COST::graph_iterator::NodesEdgesMemMapper;
+use COST::report::OrExit;
+use COST::writers::{check_outputs, AtomicFile};
+
+fn main() {
+
+    let mut args: Vec<String> = std::env::args().collect();
+    let dense = args.iter().any(|arg| arg == "--dense");
+    let lz4 = args.iter().any(|arg| arg == "--lz4");
+    let force = args.iter().any(|arg| arg == "--force");
+    args.retain(|arg| arg != "--dense" && arg != "--lz4" && arg != "--force");
+
+    if args.len() != 2 {
+        println!("Usage: to_zorder <prefix> [--dense] [--lz4] [--force]");
+        println!("NOTE: writes <prefix>.zupper and <prefix>.zlower, replacing existing files only with --force.");
+        println!("NOTE: --lz4 compresses the outputs, adding .lz4 to their names.");
+        return;
+    }
+
+    let prefix = args[1].clone();
+    let upper_name = format!("{}.zupper", prefix);
+    let lower_name = format!("{}.zlower", prefix);
+    check_outputs(&[upper_name.clone(), lower_name.clone()], force).or_exit("to_zorder");
+
+    let graph = NodesEdgesMemMapper::new(&prefix).or_exit("to_zorder");
+    let mut u_writer = AtomicFile::create(&upper_name, lz4).or_exit("to_zorder");
+    let mut l_writer = AtomicFile::create(&lower_name, lz4).or_exit("to_zorder");
+
+    let mut result = Ok(());
+    COST::zorder::convert_to_zorder(&graph, dense, |ux, uy, c, ls| {
+        if result.is_ok() { result = write_block(&mut u_writer, &mut l_writer, (ux, uy, c), ls); }
+    });
+
+    // lower first, as `HilbertGraphWriter` does, so that the upper file never counts missing edges.
+    result.and_then(|_| l_writer.commit()).and_then(|_| u_writer.commit()).or_exit("to_zorder");
+}
+
+fn write_block(upper: &mut AtomicFile, lower: &mut AtomicFile, (ux, uy, c): (u16, u16, u32), ls: &[(u16, u16)]) -> io::Result<()> {
+    upper.write_u16::<LittleEndian>(ux)?;
+    upper.write_u16::<LittleEndian>(uy)?;
+    upper.write_u32::<LittleEndian>(c)?;
+    for &(lx, ly) in ls.iter() {
+        lower.write_u16::<LittleEndian>(lx)?;
+        lower.write_u16::<LittleEndian>(ly)?;
+    }
+    Ok(())
+}
@@ -1,42 +1,64 @@
 extern crate COST;
 
-use std::fs::File;
-
-use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, NodesEdgesMemMapper, UpperLowerMemMapper };
+use COST::compression;
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper };
+use COST::typedrw::TypedMemoryMap;
+use COST::report::{Format, OrExit, Report};
 use std::io::BufReader;
 
 fn main() {
 
-    if std::env::args().len() != 4 {
-        println!("Usage: union_find  (vertex | hilbert | compressed) <prefix> nodes");
+    let mut args: Vec<String> = std::env::args().collect();
+    let format = Format::extract(&mut args);
+
+    if args.len() != 4 {
+        println!("Usage: union_find  (vertex | hilbert | zorder | compressed | compressed-mmap | varint) <prefix> nodes [--format (text | json)]");
         return;
     }
 
-    let mode = std::env::args().nth(1).expect("mode unavailable");
-    let name = std::env::args().nth(2).expect("name unavailable");
-    let nodes: u32 = std::env::args().nth(3).expect("nodes unavailable").parse().expect("nodes not parseable");
+    let mode = args[1].clone();
+    let name = args[2].clone();
+    let nodes: u32 = args[3].parse().expect("nodes not parseable");
+
+    let mut report = Report::new(format, "union_find", &mode, &name);
+    report.set("nodes", nodes);
 
     match mode.as_str() {
         "vertex" => {
-            union_find(&NodesEdgesMemMapper::new(&name), nodes)
+            union_find(&NodesEdgesMemMapper::new(&name).or_exit("union_find"), nodes, &mut report)
         },
         "hilbert" => {
-            union_find(&UpperLowerMemMapper::new(&name), nodes)
+            union_find(&UpperLowerMemMapper::new(&name).or_exit("union_find"), nodes, &mut report)
+        },
+        "zorder" => {
+            union_find(&UpperLowerMemMapper::from_files(&format!("{}.zupper", name), &format!("{}.zlower", name)).or_exit("union_find"), nodes, &mut report)
         },
         "compressed" => {
-            union_find(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&name).unwrap())), nodes)
+            union_find(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&name).or_exit("union_find"))), nodes, &mut report)
+        },
+        "compressed-mmap" => {
+            let map = TypedMemoryMap::<u8>::new(name.clone()).or_exit("union_find");
+            union_find(&DeltaCompressedSliceMapper::new(&map[..]).or_exit("union_find"), nodes, &mut report)
         },
-        _ => { println!("unrecognized mode: {:?}", mode); },
+        "varint" => {
+            union_find(&VarintMemMapper::new(&name, &format!("{}.offsets", name)).or_exit("union_find"), nodes, &mut report)
+        },
+        _ => { println!("unrecognized mode: {:?}", mode); return; },
     }
+
+    report.finish();
 }
 
-fn union_find<G: EdgeMapper>(graph: &G, nodes: u32) {
+fn union_find<G: EdgeMapper>(graph: &G, nodes: u32, report: &mut Report) {
 
     let mut roots: Vec<u32> = (0..nodes).collect();      // u32 works, and is smaller than uint/u64
     let mut ranks: Vec<u8> = vec![0u8; nodes as usize];  // u8 should be large enough (n < 2^256)
+    let mut edges = 0u64;
 
     graph.map_edges(|mut x, mut y| {
 
+        edges += 1;
+
         // x = roots[x as usize];
         // y = roots[y as usize];
         x = unsafe { *roots.get_unchecked(x as usize) };
@@ -63,7 +85,11 @@ fn union_find<G: EdgeMapper>(graph: &G, nodes: u32) {
         // roots[y as usize] = min(x, y);
     });
 
+    report.iteration();
+    report.set("edges", edges);
+
     let mut non_roots = 0u32;
     for i in 0..roots.len() { if i as u32 != roots[i] { non_roots += 1; }}
-    println!("{} non-roots found", non_roots);
+    if report.is_text() { println!("{} non-roots found", non_roots); }
+    report.result("non_roots", non_roots);
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+//! LZ4 framing for graph files.
+//!
+//! Compressed files are recognized by name: a path ending in `.lz4` holds an LZ4 frame, and any
+//! other path holds raw data. Contents are never inspected to decide, because raw graph data can
+//! begin with the bytes of the LZ4 magic number. Outputs are compressed only when asked, and the
+//! writers in `writers` then append `.lz4` to the names they were given.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+use lz4;
+use typedrw::annotate;
+
+/// The suffix of LZ4-compressed files.
+pub const LZ4_EXTENSION: &str = ".lz4";
+
+/// True if `path` names an LZ4-compressed file.
+pub fn is_lz4_path(path: &str) -> bool {
+    path.ends_with(LZ4_EXTENSION)
+}
+
+/// `path` itself, or `path` with `.lz4` appended if only that exists.
+///
+/// This lets readers given a graph's prefix find files that were written compressed. Both files
+/// existing is an error, as it is unclear which is current; the writers remove one when they
+/// commit the other.
+pub fn locate(path: &str) -> io::Result<String> {
+    if is_lz4_path(path) { return Ok(path.to_owned()); }
+    let compressed = format!("{}{}", path, LZ4_EXTENSION);
+    match (fs::metadata(path).is_ok(), fs::metadata(&compressed).is_ok()) {
+        (true, true) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: both it and {} exist", path, compressed))),
+        (false, true) => Ok(compressed),
+        _ => Ok(path.to_owned()),
+    }
+}
+
+/// Opens the file `locate` finds for `path`, decompressing it if its name ends in `.lz4`.
+///
+/// Errors name the file.
+pub fn open(path: &str) -> io::Result<Input<File>> {
+    let path = locate(path)?;
+    let file = File::open(&path).map_err(|error| annotate(error, &path))?;
+    Input::new(file, is_lz4_path(&path)).map_err(|error| annotate(error, &path))
+}
+
+/// A reader yielding the decompressed contents of an LZ4 stream, or the raw contents.
+pub enum Input<R: Read> {
+    Raw(R),
+    Lz4(lz4::Decoder<R>),
+}
+
+impl<R: Read> Input<R> {
+    /// Reads `reader` as an LZ4 stream if `lz4` is set, and as raw data otherwise.
+    pub fn new(reader: R, lz4: bool) -> io::Result<Input<R>> {
+        if lz4 { Ok(Input::Lz4(lz4::Decoder::new(reader)?)) }
+        else { Ok(Input::Raw(reader)) }
+    }
+}
+
+impl<R: Read> Read for Input<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Input::Raw(ref mut reader) => reader.read(buffer),
+            Input::Lz4(ref mut reader) => reader.read(buffer),
+        }
+    }
+}
+
+/// The decompressed contents of `bytes`, an LZ4 stream.
+pub fn decompress(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut result = Vec::new();
+    lz4::Decoder::new(bytes)?.read_to_end(&mut result)?;
+    Ok(result)
+}
+
+/// A writer that either passes bytes through or LZ4-compresses them.
+///
+/// A compressed output must be completed with `finish` to check for errors; dropping it completes
+/// the frame but, like `BufWriter`, ignores any error in doing so.
+pub struct Output<W: Write> {
+    inner: Option<OutputInner<W>>,
+}
+
+enum OutputInner<W: Write> {
+    Raw(W),
+    Lz4(lz4::Encoder<W>),
+}
+
+impl<W: Write> Output<W> {
+    pub fn new(writer: W, lz4: bool) -> io::Result<Output<W>> {
+        let inner = if lz4 { OutputInner::Lz4(lz4::EncoderBuilder::new().build(writer)?) }
+                    else { OutputInner::Raw(writer) };
+        Ok(Output { inner: Some(inner) })
+    }
+
+    /// Completes any LZ4 frame and flushes the underlying writer.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.complete()
+    }
+
+    fn complete(&mut self) -> io::Result<()> {
+        match self.inner.take() {
+            Some(OutputInner::Raw(mut writer)) => writer.flush(),
+            Some(OutputInner::Lz4(encoder)) => {
+                let (mut writer, result) = encoder.finish();
+                result?;
+                writer.flush()
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self.inner {
+            Some(OutputInner::Raw(ref mut writer)) => writer,
+            Some(OutputInner::Lz4(ref mut encoder)) => encoder,
+            None => unreachable!("output used after finish"),
+        }
+    }
+}
+
+impl<W: Write> Write for Output<W> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> { self.writer().write(buffer) }
+    fn flush(&mut self) -> io::Result<()> { self.writer().flush() }
+}
+
+impl<W: Write> Drop for Output<W> {
+    fn drop(&mut self) {
+        let _ = self.complete();
+    }
+}
@@ -0,0 +1,122 @@
+//! Synthetic graph generators, for tests and reproducible benchmarks.
+//!
+//! Each generator hands its edges to `action` one at a time, and randomized generators draw from
+//! a seeded `XorShift`, so the same arguments and seed always produce the same edges in the same order.
+
+/// xorshift64*; small, fast, and reproducible from a seed.
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // the state must be non-zero.
+        let state = seed ^ 0x9E3779B97F4A7C15;
+        XorShift { state: if state == 0 { 0x9E3779B97F4A7C15 } else { state } }
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// A uniform value in `[0, bound)`; `bound` must be positive.
+    #[inline]
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// A uniform value in `[0, 1)`.
+    #[inline]
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// The splitmix64 finalizer: a bijection on `u64` that spreads nearby inputs, such as consecutive
+/// vertex identifiers, across all 64 bits. Useful as a stateless per-vertex hash.
+#[inline]
+pub fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+/// R-MAT (Chakrabarti, Zhan, Faloutsos): `edges` edges among `2^scale` vertices, each placed by
+/// recursively choosing one of four quadrants of the adjacency matrix with probabilities `a`, `b`,
+/// `c`, and `1 - a - b - c`. The Graph500 Kronecker parameters are `(0.57, 0.19, 0.19)`.
+pub fn rmat(scale: u32, edges: u64, (a, b, c): (f64, f64, f64), rng: &mut XorShift, mut action: impl FnMut(u32, u32)) {
+    assert!(scale <= 32, "scale must be at most 32");
+    assert!(a + b + c <= 1.0, "quadrant probabilities must sum to at most one");
+    for _ in 0 .. edges {
+        let mut src = 0u32;
+        let mut dst = 0u32;
+        for bit in (0 .. scale).rev() {
+            let p = rng.next_f64();
+            if p < a { }
+            else if p < a + b { dst |= 1 << bit; }
+            else if p < a + b + c { src |= 1 << bit; }
+            else { src |= 1 << bit; dst |= 1 << bit; }
+        }
+        action(src, dst);
+    }
+}
+
+/// Erdős–Rényi G(n, m): `edges` edges with endpoints chosen uniformly among `nodes` vertices.
+pub fn erdos_renyi(nodes: u32, edges: u64, rng: &mut XorShift, mut action: impl FnMut(u32, u32)) {
+    assert!(nodes > 0, "nodes must be positive");
+    for _ in 0 .. edges {
+        let src = rng.below(nodes as u64) as u32;
+        let dst = rng.below(nodes as u64) as u32;
+        action(src, dst);
+    }
+}
+
+/// A `width` by `height` grid, with edges in both directions between horizontal and vertical
+/// neighbors. The vertex in row `r` and column `c` is `r * width + c`.
+pub fn grid(width: u32, height: u32, mut action: impl FnMut(u32, u32)) {
+    assert!((width as u64) * (height as u64) <= 1 << 32, "grid has too many vertices");
+    for row in 0 .. height {
+        for col in 0 .. width {
+            let node = row * width + col;
+            if col + 1 < width {
+                action(node, node + 1);
+                action(node + 1, node);
+            }
+            if row + 1 < height {
+                action(node, node + width);
+                action(node + width, node);
+            }
+        }
+    }
+}
+
+/// Chung-Lu power-law graph: `edges` edges whose endpoints are drawn with probability proportional
+/// to vertex weights `(i + 1)^(-1 / (exponent - 1))`, so that expected degrees follow a power law
+/// with the given exponent (which must exceed one). Low identifiers have the highest degrees.
+pub fn power_law(nodes: u32, edges: u64, exponent: f64, rng: &mut XorShift, mut action: impl FnMut(u32, u32)) {
+    assert!(nodes > 0, "nodes must be positive");
+    assert!(exponent > 1.0, "exponent must exceed one");
+
+    let mut cumulative = Vec::with_capacity(nodes as usize);
+    let mut total = 0f64;
+    for node in 0 .. nodes {
+        total += ((node + 1) as f64).powf(-1.0 / (exponent - 1.0));
+        cumulative.push(total);
+    }
+
+    let sample = |rng: &mut XorShift| {
+        let target = rng.next_f64() * total;
+        std::cmp::min(cumulative.partition_point(|&x| x <= target), nodes as usize - 1) as u32
+    };
+
+    for _ in 0 .. edges {
+        let src = sample(rng);
+        let dst = sample(rng);
+        action(src, dst);
+    }
+}
@@ -1,11 +1,50 @@
-use std::io::Read;
-use hilbert_curve::BytewiseCached;
-use typedrw::TypedMemoryMap;
+use std::cell::Cell;
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use hilbert_curve::{BytewiseCached, DEFAULT_LOW_BITS, SPLIT_HEADER_BYTES, read_low_bits, split_record_bytes};
+use typedrw::{LittleEndianData, TypedMemoryMap};
 
 pub trait EdgeMapper {
     fn map_edges(&self, action: impl FnMut(u32, u32));
 }
 
+/// A `.nodes` record: a source and the number of its edges that follow in `.edges`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NodeRecord {
+    pub node:   u32,
+    pub count:  u32,
+}
+
+/// An `.upper` record: the high 16 bits of a block's coordinates, and the number of its edges in `.lower`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct UpperRecord {
+    pub x:      u16,
+    pub y:      u16,
+    pub count:  u32,
+}
+
+/// A `.lower` record: the low 16 bits of an edge's coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LowerRecord {
+    pub x:      u16,
+    pub y:      u16,
+}
+
+impl LittleEndianData for NodeRecord {
+    #[inline] fn swap_bytes(self) -> NodeRecord { NodeRecord { node: self.node.swap_bytes(), count: self.count.swap_bytes() } }
+}
+impl LittleEndianData for UpperRecord {
+    #[inline] fn swap_bytes(self) -> UpperRecord { UpperRecord { x: self.x.swap_bytes(), y: self.y.swap_bytes(), count: self.count.swap_bytes() } }
+}
+impl LittleEndianData for LowerRecord {
+    #[inline] fn swap_bytes(self) -> LowerRecord { LowerRecord { x: self.x.swap_bytes(), y: self.y.swap_bytes() } }
+}
+
+/// Decodes a delta-compressed file from a fresh reader on each pass; `compression::open` gives
+/// readers that decompress `.lz4` files.
 pub struct DeltaCompressedReaderMapper<R: Read, F: Fn()->R> {
     reader: F,
 }
@@ -29,11 +68,14 @@ impl<R: Read, F: Fn()->R> EdgeMapper for DeltaCompressedReaderMapper<R, F> {
         let mut depth = 0u8;     // for counting number of zeros
 
         let mut buffer = vec![0u8; 1 << 16];
-        while let Ok(read) = reader.read(&mut buffer[..]) {
-            if read == 0 {
-                // Reached EOF.
-                break;
-            }
+        loop {
+            // a failed read would otherwise look like the end of the graph.
+            let read = match reader.read(&mut buffer[..]) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => panic!("read error: {}", error),
+            };
             for &byte in &buffer[..read] {
                 if byte == 0 && delta == 0 {
                     depth += 1;
@@ -60,50 +102,111 @@ pub struct DeltaCompressedSliceMapper<'a> {
 }
 
 impl<'a> DeltaCompressedSliceMapper<'a> {
-    pub fn new(slice: &'a [u8]) -> DeltaCompressedSliceMapper<'a> {
-        DeltaCompressedSliceMapper {
-            slice: slice,
+    /// Checks that `slice` holds only complete deltas of at most 64 bits, so that decoding it cannot
+    /// run off its end; this walks the bytes once, without decoding the edges.
+    pub fn new(slice: &'a [u8]) -> io::Result<DeltaCompressedSliceMapper<'a>> {
+        let mut cursor = 0;
+        while cursor < slice.len() {
+            let byte = slice[cursor];
+            cursor += 1;
+            if byte == 0 {
+                let mut depth = 2;
+                while cursor < slice.len() && slice[cursor] == 0 {
+                    cursor += 1;
+                    depth += 1;
+                }
+                if depth > 8 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("compressed graph corrupt: delta of {} bytes at byte {}", depth, cursor)));
+                }
+                cursor += depth;
+            }
+        }
+        if cursor > slice.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("compressed graph truncated: last delta runs past the end of its {} bytes", slice.len())));
         }
+
+        Ok(DeltaCompressedSliceMapper {
+            slice: slice,
+        })
     }
 }
 
-impl<'a> EdgeMapper for DeltaCompressedSliceMapper<'a> {
-    fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
-
+impl<'a> DeltaCompressedSliceMapper<'a> {
+    /// As `map_edges`, but detangling each index as soon as it is decoded rather than in batches,
+    /// for measuring what batching gains.
+    pub fn map_edges_unbatched(&self, mut action: impl FnMut(u32, u32)) {
         let mut hilbert = BytewiseCached::new();
-        let mut current = 0u64;
+        self.map_indices(|tangle| {
+            let (x, y) = hilbert.detangle(tangle);
+            action(x, y);
+        });
+    }
 
+    // decodes the deltas of the slice, and applies `action` to each running total.
+    #[inline(always)]
+    fn map_indices(&self, mut action: impl FnMut(u64)) {
+        let mut current = 0u64;
         let mut cursor = 0;
         while cursor < self.slice.len() {
-            let byte = unsafe { *self.slice.get_unchecked(cursor) };
+            let byte = self.slice[cursor];
             cursor += 1;
 
             if byte > 0 {
                 current += byte as u64;
-                let (x,y) = hilbert.detangle(current);
-                action(x,y);
             }
             else {
                 let mut depth = 2;
-                while unsafe { *self.slice.get_unchecked(cursor) } == 0 {
+                while self.slice[cursor] == 0 {
                     cursor += 1;
                     depth += 1;
                 }
                 let mut delta = 0u64;
                 while depth > 0 {
-                    delta = (delta << 8) + (unsafe { *self.slice.get_unchecked(cursor) } as u64);
+                    delta = (delta << 8) + (self.slice[cursor] as u64);
                     cursor += 1;
                     depth -= 1;
                 }
 
                 current += delta;
-                let (x,y) = hilbert.detangle(current);
-                action(x,y);
             }
+
+            action(current);
         }
     }
 }
 
+impl<'a> EdgeMapper for DeltaCompressedSliceMapper<'a> {
+    fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
+
+        // indices are decoded a batch at a time, and then detangled together.
+        const BATCH: usize = 1024;
+
+        let mut hilbert = BytewiseCached::new();
+        let mut tangles = Vec::with_capacity(BATCH);
+        let mut xs = vec![0u32; BATCH];
+        let mut ys = vec![0u32; BATCH];
+
+        self.map_indices(|tangle| {
+            tangles.push(tangle);
+            if tangles.len() == BATCH {
+                detangle_batch(&mut hilbert, &mut tangles, &mut xs, &mut ys, &mut action);
+            }
+        });
+        detangle_batch(&mut hilbert, &mut tangles, &mut xs, &mut ys, &mut action);
+    }
+}
+
+// detangles and applies `action` to the edges of `tangles`, leaving it empty.
+#[inline]
+fn detangle_batch(hilbert: &mut BytewiseCached, tangles: &mut Vec<u64>, xs: &mut [u32], ys: &mut [u32], action: &mut impl FnMut(u32, u32)) {
+    let count = tangles.len();
+    hilbert.detangle_batch(&tangles[..], &mut xs[.. count], &mut ys[.. count]);
+    for index in 0 .. count {
+        action(xs[index], ys[index]);
+    }
+    tangles.clear();
+}
+
 // // naughty method using unsafe transmute to read a filled binary buffer as a typed buffer
 // fn read_as_typed<'a, R: Read, T: Copy>(reader: &mut R, buffer: &'a mut[u8]) -> Result<&'a[T]> {
 //     if mem::size_of::<T>() * (buffer.len() / mem::size_of::<T>()) < buffer.len() {
@@ -178,44 +281,241 @@ impl<'a> EdgeMapper for DeltaCompressedSliceMapper<'a> {
 // }
 
 pub struct UpperLowerMemMapper {
-    upper:  TypedMemoryMap<((u16,u16), u32)>,
-    lower:  TypedMemoryMap<(u16, u16)>,
+    blocks: Blocks,
+}
+
+// the records of an upper/lower layout, as split at `hilbert_curve::DEFAULT_LOW_BITS` or elsewhere.
+enum Blocks {
+    Halves {
+        upper:  TypedMemoryMap<UpperRecord>,
+        lower:  TypedMemoryMap<LowerRecord>,
+    },
+    Split {
+        low_bits:   u32,
+        upper:      TypedMemoryMap<u32>,
+        lower:      TypedMemoryMap<u8>,
+    },
 }
 
 impl UpperLowerMemMapper {
-    pub fn new(graph_name: &str) -> UpperLowerMemMapper {
-        UpperLowerMemMapper {
-            upper: TypedMemoryMap::new(format!("{}.upper", graph_name)),
-            lower: TypedMemoryMap::new(format!("{}.lower", graph_name)),
+    pub fn new(graph_name: &str) -> io::Result<UpperLowerMemMapper> {
+        UpperLowerMemMapper::from_files(&format!("{}.upper", graph_name), &format!("{}.lower", graph_name))
+    }
+
+    /// Reads upper and lower records from the named files, as for the `.zupper` and `.zlower` of `to_zorder`.
+    ///
+    /// The split of the records is read from the upper file's header, as by `hilbert_curve::read_low_bits`,
+    /// and the edge counts of its blocks must add up to the number of lower records.
+    pub fn from_files(upper_name: &str, lower_name: &str) -> io::Result<UpperLowerMemMapper> {
+        let low_bits = read_low_bits(upper_name)?;
+        let (blocks, counted, edges) = if low_bits == DEFAULT_LOW_BITS {
+            let upper = TypedMemoryMap::<UpperRecord>::new_le(upper_name.to_owned())?;
+            let lower = TypedMemoryMap::<LowerRecord>::new_le(lower_name.to_owned())?;
+            let counted = upper[..].iter().map(|record| record.count as u64).sum::<u64>();
+            let edges = lower[..].len() as u64;
+            (Blocks::Halves { upper, lower }, counted, edges)
+        }
+        else {
+            let upper = TypedMemoryMap::<u32>::new_le(upper_name.to_owned())?;
+            let lower = TypedMemoryMap::<u8>::new(lower_name.to_owned())?;
+            let (upper_bytes, lower_bytes) = split_record_bytes(low_bits);
+            let records = upper[..].len() as u64 * 4 - SPLIT_HEADER_BYTES as u64;
+            for &(name, bytes, record) in &[(upper_name, records, upper_bytes), (lower_name, lower[..].len() as u64, lower_bytes)] {
+                if bytes % record != 0 {
+                    let message = format!("{}: length {} is not a multiple of {} bytes", name, bytes, record);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+            let counted = upper[..][SPLIT_HEADER_BYTES / 4 ..].chunks(3).map(|record| record[2] as u64).sum::<u64>();
+            let edges = lower[..].len() as u64 / lower_bytes;
+            (Blocks::Split { low_bits, upper, lower }, counted, edges)
+        };
+
+        // a mismatch means the files were not written together, or one of them was damaged since.
+        if counted != edges {
+            let message = format!("{}: blocks hold {} edges, but {} holds {}", upper_name, counted, lower_name, edges);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        Ok(UpperLowerMemMapper { blocks })
+    }
+
+    /// The number of low bits of each coordinate held by lower records.
+    pub fn low_bits(&self) -> u32 {
+        match self.blocks {
+            Blocks::Halves { .. } => DEFAULT_LOW_BITS,
+            Blocks::Split { low_bits, .. } => low_bits,
+        }
+    }
+
+    // calls `block` with the high bits of each block's coordinates, and if it returns true, `action`
+    // with each of the block's edges.
+    #[inline(always)]
+    fn map_blocks(&self, mut block: impl FnMut(u32, u32) -> bool, mut action: impl FnMut(u32, u32)) {
+        match self.blocks {
+            Blocks::Halves { ref upper, ref lower } => {
+                let mut slice = &lower[..];
+                for &UpperRecord { x: u16_x, y: u16_y, count } in &upper[..] {
+                    let (edges, rest) = slice.split_at(count as usize);
+                    slice = rest;
+
+                    let u16_x = (u16_x as u32) << 16;
+                    let u16_y = (u16_y as u32) << 16;
+                    if block(u16_x, u16_y) {
+                        for &LowerRecord { x: l16_x, y: l16_y } in edges {
+                            action(u16_x | l16_x as u32, u16_y | l16_y as u32);
+                        }
+                    }
+                }
+            },
+            Blocks::Split { low_bits, ref upper, ref lower } => {
+                let width = split_record_bytes(low_bits).1 as usize;
+                let mask = (1u64 << low_bits) - 1;
+                let mut slice = &lower[..];
+                for record in upper[..][SPLIT_HEADER_BYTES / 4 ..].chunks(3) {
+                    let (edges, rest) = slice.split_at(record[2] as usize * width);
+                    slice = rest;
+
+                    let high_x = record[0] << low_bits;
+                    let high_y = record[1] << low_bits;
+                    if block(high_x, high_y) {
+                        for bytes in edges.chunks(width) {
+                            let mut packed = 0u64;
+                            for (index, &byte) in bytes.iter().enumerate() { packed |= (byte as u64) << (8 * index); }
+                            action(high_x | (packed >> low_bits) as u32, high_y | (packed & mask) as u32);
+                        }
+                    }
+                }
+            },
         }
     }
 }
 
 impl EdgeMapper for UpperLowerMemMapper {
-    fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
-        let mut slice = &self.lower[..];
-        for &((u16_x, u16_y), count) in &self.upper[..] {
-            let u16_x = (u16_x as u32) << 16;
-            let u16_y = (u16_y as u32) << 16;
-            for &(l16_x, l16_y) in &slice[.. count as usize] {
-                action(u16_x | l16_x as u32, u16_y | l16_y as u32);
+    fn map_edges(&self, action: impl FnMut(u32, u32)) {
+        self.map_blocks(|_, _| true, action);
+    }
+}
+
+impl UpperLowerMemMapper {
+    /// Applies `action` to each edge whose source lies in `sources` and whose target lies in `targets`.
+    ///
+    /// Blocks that miss either range are skipped without reading their edges, and edges of blocks
+    /// that lie entirely within both ranges are not tested individually.
+    pub fn map_edges_in(&self, sources: RangeInclusive<u32>, targets: RangeInclusive<u32>, mut action: impl FnMut(u32, u32)) {
+        let (x_lo, x_hi) = (*sources.start(), *sources.end());
+        let (y_lo, y_hi) = (*targets.start(), *targets.end());
+        let span = ((1u64 << self.low_bits()) - 1) as u32;
+
+        let filter = Cell::new(true);
+        self.map_blocks(|x, y| {
+            if x > x_hi || (x | span) < x_lo || y > y_hi || (y | span) < y_lo {
+                return false;
+            }
+            filter.set(!(x_lo <= x && (x | span) <= x_hi && y_lo <= y && (y | span) <= y_hi));
+            true
+        },
+        |x, y| {
+            if !filter.get() || (x_lo <= x && x <= x_hi && y_lo <= y && y <= y_hi) {
+                action(x, y);
             }
+        });
+    }
 
-            slice = &slice[count as usize ..];
+    /// The subgraph of edges from `sources` to `targets`, scanned as `map_edges_in` does.
+    pub fn restrict<'a>(&'a self, sources: RangeInclusive<u32>, targets: RangeInclusive<u32>) -> UpperLowerRange<'a> {
+        UpperLowerRange {
+            graph: self,
+            sources,
+            targets,
         }
     }
 }
 
+/// The edges of an `UpperLowerMemMapper` between two vertex ranges.
+pub struct UpperLowerRange<'a> {
+    graph:      &'a UpperLowerMemMapper,
+    sources:    RangeInclusive<u32>,
+    targets:    RangeInclusive<u32>,
+}
+
+impl<'a> EdgeMapper for UpperLowerRange<'a> {
+    fn map_edges(&self, action: impl FnMut(u32, u32)) {
+        self.graph.map_edges_in(self.sources.clone(), self.targets.clone(), action);
+    }
+}
+
 pub struct NodesEdgesMemMapper {
-    nodes:  TypedMemoryMap<(u32, u32)>,
+    nodes:  TypedMemoryMap<NodeRecord>,
     edges:  TypedMemoryMap<u32>,
 }
 
 impl NodesEdgesMemMapper {
-    pub fn new(graph_name: &str) -> NodesEdgesMemMapper {
-        NodesEdgesMemMapper {
-            nodes: TypedMemoryMap::new(format!("{}.nodes", graph_name)),
-            edges: TypedMemoryMap::new(format!("{}.edges", graph_name)),
+    pub fn new(graph_name: &str) -> io::Result<NodesEdgesMemMapper> {
+        let nodes_name = format!("{}.nodes", graph_name);
+        let edges_name = format!("{}.edges", graph_name);
+        let nodes = TypedMemoryMap::<NodeRecord>::new_le(nodes_name.clone())?;
+        let edges = TypedMemoryMap::<u32>::new_le(edges_name.clone())?;
+
+        // as for the upper and lower files, a mismatch means the files were not written together.
+        let counted: u64 = nodes[..].iter().map(|record| record.count as u64).sum();
+        if counted != edges[..].len() as u64 {
+            let message = format!("{}: nodes hold {} edges, but {} holds {}", nodes_name, counted, edges_name, edges[..].len());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        Ok(NodesEdgesMemMapper { nodes, edges })
+    }
+
+    /// Applies `action` to each node with outgoing edges, along with its adjacency list.
+    pub fn map_nodes(&self, mut action: impl FnMut(u32, &[u32])) {
+        let mut slice = &self.edges[..];
+        for &NodeRecord { node, count } in &self.nodes[..] {
+            action(node, &slice[.. count as usize]);
+            slice = &slice[count as usize ..];
+        }
+    }
+
+    /// Builds an index providing random access to the adjacency lists of nodes less than `nodes`.
+    ///
+    /// The `.nodes` file may list nodes in any order, as `to_vertex` writes them for unsorted input,
+    /// but each at most once, so that its edges are contiguous; other files are refused.
+    pub fn index<'a>(&'a self, nodes: u32) -> io::Result<NodesEdgesIndex<'a>> {
+        let unlisted = (usize::MAX, usize::MAX);
+        let mut ranges = vec![unlisted; nodes as usize];
+        let mut offset = 0;
+        for &NodeRecord { node, count } in &self.nodes[..] {
+            if node >= nodes {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("node {} is not less than {}", node, nodes)));
+            }
+            if ranges[node as usize] != unlisted {
+                let message = format!("node {} has edges in more than one record; convert input listing each source's edges together", node);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+            }
+            ranges[node as usize] = (offset, offset + count as usize);
+            offset += count as usize;
+        }
+
+        Ok(NodesEdgesIndex {
+            ranges,
+            edges: &self.edges[..],
+        })
+    }
+}
+
+/// Random access to the adjacency lists of a `NodesEdgesMemMapper`.
+pub struct NodesEdgesIndex<'a> {
+    ranges:     Vec<(usize, usize)>,    // the bounds of each node's edges, or usize::MAX for nodes without
+    edges:      &'a [u32],
+}
+
+impl<'a> NodesEdgesIndex<'a> {
+    /// The out-neighbors of `node`.
+    #[inline]
+    pub fn neighbors(&self, node: u32) -> &'a [u32] {
+        match self.ranges[node as usize] {
+            (usize::MAX, _) => &[],
+            (lower, upper) => &self.edges[lower .. upper],
         }
     }
 }
@@ -223,7 +523,7 @@ impl NodesEdgesMemMapper {
 impl EdgeMapper for NodesEdgesMemMapper {
     fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
         let mut slice = &self.edges[..];
-        for &(node, count) in &self.nodes[..] {
+        for &NodeRecord { node, count } in &self.nodes[..] {
             for &edge in &slice[.. count as usize] {
                 action(node, edge);
             }
@@ -233,6 +533,128 @@ impl EdgeMapper for NodesEdgesMemMapper {
     }
 }
 
+/// The adjacency format written by `compressed scan`: an offsets file of little-endian `u64`s, whose
+/// entry `k` is the byte at which node `k`'s list ends and node `k + 1`'s begins, and a data file of
+/// sorted neighbors, each stored as its difference from the previous neighbor (from zero at the start
+/// of every list) in little-endian base-128 varints, with the high bit set on all but the last byte.
+pub struct VarintMemMapper {
+    offsets:    TypedMemoryMap<u64>,
+    data:       TypedMemoryMap<u8>,
+}
+
+impl VarintMemMapper {
+    /// Checks that the offsets never decrease or pass the end of the data, and that each list holds
+    /// only complete neighbors of at most 32 bits, so that iterating cannot fail; this decodes every
+    /// list once.
+    pub fn new(data_name: &str, offsets_name: &str) -> io::Result<VarintMemMapper> {
+        let mapper = VarintMemMapper {
+            offsets: TypedMemoryMap::new_le(offsets_name.to_owned())?,
+            data: TypedMemoryMap::new(data_name.to_owned())?,
+        };
+
+        let data = &mapper.data[..];
+        let invalid = |name: &str, message: String| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", name, message));
+        let mut lower = 0;
+        for (node, &upper) in mapper.offsets[..].iter().chain(Some(&(data.len() as u64))).enumerate() {
+            if upper < lower || upper > data.len() as u64 {
+                return Err(invalid(offsets_name, format!("node {} ends at byte {}, after {} but within {} bytes", node, upper, lower, data.len())));
+            }
+            let mut current = 0u64;
+            let mut delta = 0u64;
+            let mut shift = 0;
+            for (index, &byte) in data[lower as usize .. upper as usize].iter().enumerate() {
+                if shift == 35 {
+                    return Err(invalid(data_name, format!("neighbor longer than five bytes at byte {}", lower as usize + index)));
+                }
+                delta |= ((byte & 127) as u64) << shift;
+                shift += 7;
+                if byte < 128 {
+                    current += delta;
+                    if current > u32::MAX as u64 {
+                        return Err(invalid(data_name, format!("neighbor of node {} exceeds 32 bits at byte {}", node, lower as usize + index)));
+                    }
+                    delta = 0;
+                    shift = 0;
+                }
+            }
+            if shift > 0 {
+                return Err(invalid(data_name, format!("list of node {} ends mid-neighbor at byte {}", node, upper)));
+            }
+            lower = upper;
+        }
+
+        Ok(mapper)
+    }
+
+    /// One more than the largest node with a list; lists of smaller nodes may be empty.
+    pub fn nodes(&self) -> u32 {
+        let offsets = &self.offsets[..];
+        if offsets.is_empty() && self.data[..].is_empty() { 0 } else { offsets.len() as u32 + 1 }
+    }
+
+    /// The out-neighbors of `node`, decoded as they are iterated.
+    pub fn neighbors<'a>(&'a self, node: u32) -> VarintNeighbors<'a> {
+        let offsets = &self.offsets[..];
+        let data = &self.data[..];
+        let node = node as usize;
+
+        // `new` checked that these bounds are in order and within the data.
+        let lower = if node == 0 { 0 } else { offsets.get(node - 1).map(|&x| x as usize).unwrap_or(data.len()) };
+        let upper = offsets.get(node).map(|&x| x as usize).unwrap_or(data.len());
+
+        VarintNeighbors {
+            data: &data[lower .. upper],
+            current: 0,
+        }
+    }
+}
+
+impl EdgeMapper for VarintMemMapper {
+    fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
+        for node in 0 .. self.nodes() {
+            for neighbor in self.neighbors(node) {
+                action(node, neighbor);
+            }
+        }
+    }
+}
+
+/// Iterates over one adjacency list of a `VarintMemMapper`.
+pub struct VarintNeighbors<'a> {
+    data:       &'a [u8],
+    current:    u32,
+}
+
+impl<'a> Iterator for VarintNeighbors<'a> {
+    type Item = u32;
+    #[inline]
+    fn next(&mut self) -> Option<u32> {
+        if self.data.is_empty() { return None; }
+
+        // `new` checked that every neighbor is complete and fits in 32 bits.
+        let mut delta = 0u32;
+        let mut shift = 0;
+        while let Some((&byte, rest)) = self.data.split_first() {
+            self.data = rest;
+            delta |= ((byte & 127) as u32) << shift;
+            shift += 7;
+            if byte < 128 { break; }
+        }
+
+        self.current += delta;
+        Some(self.current)
+    }
+}
+
+impl EdgeMapper for Vec<(u32, u32)> {
+    fn map_edges(&self, mut action: impl FnMut(u32, u32)) {
+        for &(src, dst) in self.iter() {
+            action(src, dst);
+        }
+    }
+}
+
+/// Parses lines of whitespace-separated `src dst` pairs, skipping lines starting with `#`.
 pub struct ReaderMapper<B: ::std::io::BufRead, F: Fn() -> B> {
     pub reader: F,
 }
@@ -1,22 +1,32 @@
-use std::io::{Read, Write};
-use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::RangeInclusive;
+use compression;
+use typedrw::annotate;
 use graph_iterator::EdgeMapper;
 use byteorder::{ReadBytesExt, WriteBytesExt};
 
 #[inline]
 pub fn encode<W: Write>(writer: &mut W, diff: u64) {
+    try_encode(writer, diff).ok().expect("write error");
+}
+
+/// As `encode`, but returning write errors rather than panicking.
+#[inline]
+pub fn try_encode<W: Write>(writer: &mut W, diff: u64) -> io::Result<()> {
     assert!(diff > 0);
     for &shift in [56, 48, 40, 32, 24, 16, 8].iter() {
         if (diff >> shift) != 0 {
-            writer.write_u8(0u8).ok().expect("write error");
+            writer.write_u8(0u8)?;
         }
     }
     for &shift in [56, 48, 40, 32, 24, 16, 8].iter() {
         if (diff >> shift) != 0 {
-            writer.write_u8((diff >> shift) as u8).ok().expect("write error");
+            writer.write_u8((diff >> shift) as u8)?;
         }
     }
-    writer.write_u8(diff as u8).ok().expect("write error");
+    writer.write_u8(diff as u8)
 }
 
 #[inline]
@@ -89,24 +99,40 @@ where I : EdgeMapper,
     for &element in buffer.iter() { output(element); }
 }
 
+// renames vertices densely from zero, in order of first appearance, for the converters' `make_dense`.
+pub(crate) struct DenseNames {
+    names:  Vec<u32>,
+    count:  u32,
+}
+
+impl DenseNames {
+    pub(crate) fn new() -> DenseNames {
+        DenseNames { names: Vec::new(), count: 0 }
+    }
+
+    // the new name of `node`, giving it the next unused name if it has none yet.
+    pub(crate) fn rename(&mut self, node: u32) -> u32 {
+        if self.names.len() <= node as usize { self.names.resize(node as usize + 1, u32::MAX); }
+        if self.names[node as usize] == u32::MAX {
+            self.names[node as usize] = self.count;
+            self.count += 1;
+        }
+        self.names[node as usize]
+    }
+}
+
 pub fn convert_to_hilbert<I, O>(graph: &I, make_dense: bool, mut output: O) -> ()
 where I : EdgeMapper,
       O : FnMut(u16, u16, u32, &Vec<(u16, u16)>) -> (),
 {
     let mut uppers: HashMap<u32,Vec<u32>> = HashMap::new();
-    let mut names = Vec::new();
-    let mut names_count = 0i32;
+    let mut names = DenseNames::new();
     let hilbert = BytewiseHilbert::new();
 
     graph.map_edges(|mut node, mut edge| {
         if make_dense {
-            while names.len() as u32 <= node { names.push(-1i32); }
-            while names.len() as u32 <= edge { names.push(-1i32); }
-            if names[node as usize] == -1i32 { names[node as usize] = names_count; names_count += 1; }
-            if names[edge as usize] == -1i32 { names[edge as usize] = names_count; names_count += 1; }
-
-            node = names[node as usize] as u32;
-            edge = names[edge as usize] as u32;
+            node = names.rename(node);
+            edge = names.rename(edge);
         }
 
         let entangled = hilbert.entangle((node as u32, edge as u32));
@@ -143,41 +169,308 @@ where I : EdgeMapper,
     }
 }
 
+/// The number of low bits of each coordinate that `convert_to_hilbert` leaves to lower records.
+pub const DEFAULT_LOW_BITS: u32 = 16;
+
+/// The bytes opening an upper file split at other than `DEFAULT_LOW_BITS`, which are followed by the
+/// split as a little-endian `u32` and four zero bytes; see `split_header`.
+pub const SPLIT_MAGIC: [u8; 8] = *b"COSTsplt";
+
+/// The length of the header opening an upper file split at other than `DEFAULT_LOW_BITS`.
+pub const SPLIT_HEADER_BYTES: usize = 16;
+
+/// The header opening an upper file split at `low_bits`, which must not be `DEFAULT_LOW_BITS`.
+///
+/// Files with the default split have no header, as they always have. The header keeps the split
+/// with the records it describes, so that the file cannot be read with the wrong record sizes.
+pub fn split_header(low_bits: u32) -> [u8; SPLIT_HEADER_BYTES] {
+    assert!(low_bits != DEFAULT_LOW_BITS, "the default split has no header");
+    let mut header = [0u8; SPLIT_HEADER_BYTES];
+    header[.. 8].copy_from_slice(&SPLIT_MAGIC);
+    header[8 .. 12].copy_from_slice(&low_bits.to_le_bytes());
+    header
+}
+
+/// Reads the split of the named upper file from its header, or `DEFAULT_LOW_BITS` if it has none.
+pub fn read_low_bits(upper_name: &str) -> io::Result<u32> {
+    let annotate = |error| annotate(error, upper_name);
+    let reader = compression::open(upper_name)?;
+    let mut header = Vec::with_capacity(SPLIT_HEADER_BYTES);
+    reader.take(SPLIT_HEADER_BYTES as u64).read_to_end(&mut header).map_err(annotate)?;
+
+    if header.len() < SPLIT_HEADER_BYTES || header[.. 8] != SPLIT_MAGIC {
+        return Ok(DEFAULT_LOW_BITS);
+    }
+    let low_bits = u32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    if (8 ..= 24).contains(&low_bits) && low_bits != DEFAULT_LOW_BITS && header[12 ..] == [0u8; 4] {
+        Ok(low_bits)
+    }
+    else {
+        Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}: unsupported split header {:?}", upper_name, header)))
+    }
+}
+
+/// The bytes of each upper and lower record for blocks split at `low_bits`.
+///
+/// The default split keeps its `UpperRecord` and `LowerRecord` records. Others use three
+/// little-endian `u32`s for upper coordinates and count, and pack each `(x, y)` lower pair into
+/// the fewest bytes holding `2 * low_bits` bits, as the little-endian integer `x << low_bits | y`.
+pub fn split_record_bytes(low_bits: u32) -> (u64, u64) {
+    if low_bits == DEFAULT_LOW_BITS { (8, 4) } else { (12, (2 * low_bits).div_ceil(8) as u64) }
+}
+
+/// As `convert_to_hilbert`, but splitting coordinates at `low_bits` rather than `DEFAULT_LOW_BITS`,
+/// so that blocks hold `1 << low_bits` by `1 << low_bits` pairs. Coordinates are not truncated.
+pub fn convert_to_hilbert_split<I, O>(graph: &I, make_dense: bool, low_bits: u32, mut output: O)
+where I : EdgeMapper,
+      O : FnMut(u32, u32, u32, &Vec<(u32, u32)>),
+{
+    assert!((8 ..= 24).contains(&low_bits), "unsupported split: {} low bits", low_bits);
+
+    let mut uppers: HashMap<u64,Vec<u64>> = HashMap::new();
+    let mut names = DenseNames::new();
+    let hilbert = BytewiseHilbert::new();
+    let mask = (1u64 << (2 * low_bits)) - 1;
+
+    graph.map_edges(|mut node, mut edge| {
+        if make_dense {
+            node = names.rename(node);
+            edge = names.rename(edge);
+        }
+
+        let entangled = hilbert.entangle((node, edge));
+        uppers.entry(entangled >> (2 * low_bits)).or_default().push(entangled & mask);
+    });
+
+    let mut keys: Vec<u64> = uppers.keys().cloned().collect();
+    keys.sort();
+
+    let mut temp = Vec::new();
+    for &upper in keys.iter() {
+        let mut lowers = uppers.remove(&upper).unwrap();
+        let base = upper << (2 * low_bits);
+        let (upperx, uppery) = hilbert.detangle(base);
+
+        lowers.sort();
+        temp.clear();
+        for &lower in lowers.iter() {
+            let (x, y) = hilbert.detangle(base | lower);
+            temp.push((x & ((1 << low_bits) - 1), y & ((1 << low_bits) - 1)));
+        }
+
+        output(upperx >> low_bits, uppery >> low_bits, lowers.len() as u32, &temp);
+    }
+}
+
+/// The bytes of the upper and lower files `graph` would need for each split from 8 to 24 low bits.
+///
+/// Blocks are counted in one pass over the edges, which must arrive in Hilbert order, as from the
+/// Hilbert and compressed formats: consecutive indices first differing at bit `b` lie in different
+/// blocks exactly for splits of at most `b / 2` low bits. Edges out of order are an error.
+pub fn split_bytes<I: EdgeMapper>(graph: &I) -> io::Result<Vec<(u32, u64)>> {
+    let hilbert = BytewiseHilbert::new();
+    let mut edges = 0u64;
+    let mut previous: Option<u64> = None;
+    let mut ordered = true;
+
+    // blocks[low_bits] counts the blocks for splits of `low_bits`.
+    let mut blocks = vec![0u64; 33];
+    graph.map_edges(|x, y| {
+        let tangle = hilbert.entangle((x, y));
+        match previous {
+            None => { for count in blocks.iter_mut() { *count = 1; } },
+            Some(prev) if prev < tangle => {
+                let highest = 63 - (prev ^ tangle).leading_zeros();
+                for count in blocks[..= (highest / 2) as usize].iter_mut() { *count += 1; }
+            },
+            Some(prev) if prev > tangle => { ordered = false; },
+            Some(_) => { },
+        }
+        previous = Some(tangle);
+        edges += 1;
+    });
+
+    if !ordered {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "edges are not in Hilbert order; size splits from a Hilbert or compressed graph"));
+    }
+
+    Ok((8 ..= 24).map(|low_bits| {
+        let (upper, lower) = split_record_bytes(low_bits);
+        let header = if low_bits == DEFAULT_LOW_BITS { 0 } else { SPLIT_HEADER_BYTES as u64 };
+        (low_bits, header + blocks[low_bits as usize] * upper + edges * lower)
+    }).collect())
+}
+
+/// Merges increasing sequences of values into one non-decreasing sequence, handed to `output`.
+///
+/// A binary heap holds the next value of each sequence, so each output costs O(log k) for k sequences.
+/// Values common to several sequences are output once per sequence.
 pub fn merge<I: Iterator<Item=u64>, O: FnMut(u64)->()>(mut iterators: Vec<I>, mut output: O) {
-    let mut values = Vec::new();
-    for iterator in iterators.iter_mut() { values.push(iterator.next()); }
+    let mut heap = BinaryHeap::with_capacity(iterators.len());
+    for (index, iterator) in iterators.iter_mut().enumerate() {
+        if let Some(value) = iterator.next() { heap.push(Reverse((value, index))); }
+    }
 
     let mut val_old = 0;
-    let mut done = false;
-    while !done {
-        let mut arg_min = iterators.len();
-        let mut val_min = 0u64;
-        for (index, &value) in values.iter().enumerate() {
-            if let Some(val) = value {
-                if arg_min > index || val < val_min {
-                    arg_min = index;
-                    val_min = val;
-                    // done = false;
+    while let Some(Reverse((val_min, arg_min))) = heap.pop() {
+        if let Some(val) = iterators[arg_min].next() {
+            assert!(val > val_min);
+            heap.push(Reverse((val, arg_min)));
+        }
+        assert!(val_old <= val_min);
+        val_old = val_min;
+        output(val_min);
+    }
+}
+
+/// Byte offsets into a delta-compressed stream from which decoding can resume, one every `stride`
+/// values, each paired with the value just before it. The first entry is always `(0, 0)`.
+pub fn skip_index(bytes: &[u8], stride: usize) -> Vec<(usize, u64)> {
+    let mut index = vec![(0, 0)];
+    let mut cursor = 0;
+    let mut current = 0u64;
+    let mut count = 0;
+    // as `decode`, but walking the slice directly; this pass sees every byte of every source.
+    while cursor < bytes.len() {
+        let mut zeros = 0;
+        while bytes[cursor] == 0 {
+            zeros += 1;
+            cursor += 1;
+        }
+        let mut diff = 0u64;
+        for _ in 0 ..= zeros {
+            diff = (diff << 8) + bytes[cursor] as u64;
+            cursor += 1;
+        }
+        current += diff;
+        count += 1;
+        if count % stride == 0 { index.push((cursor, current)); }
+    }
+    index
+}
+
+/// Merges sorted delta-compressed streams into one delta stream written to `writer`, dropping
+/// duplicates, with `threads` threads sharing the work.
+///
+/// Each source is decoded once to build its `skip_index`. The values at the skip points of all
+/// sources are pooled to cut the index range into parts holding similar numbers of values, and each
+/// part is merged by decoding every source from its last skip point below the part. Threads take
+/// parts in turn and the parts are written in order as they complete, so that at most two parts per
+/// thread are held in memory.
+pub fn merge_parallel<W: Write>(sources: &[&[u8]], threads: usize, writer: &mut W) -> io::Result<()> {
+    merge_parallel_with(sources, threads, 1 << 12, writer)
+}
+
+// as `merge_parallel`, with a skip point every `stride` values.
+fn merge_parallel_with<W: Write>(sources: &[&[u8]], threads: usize, stride: usize, writer: &mut W) -> io::Result<()> {
+
+    // parts of about this many values keep the buffered parts small, while leaving skipping cheap.
+    const PART_VALUES: usize = 1 << 22;
+
+    let threads = std::cmp::max(threads, 1);
+
+    let indices: Vec<Vec<(usize, u64)>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0 .. threads).map(|worker| {
+            scope.spawn(move || {
+                sources.iter().enumerate().skip(worker).step_by(threads).map(|(number, &source)| (number, skip_index(source, stride))).collect::<Vec<_>>()
+            })
+        }).collect();
+        let mut indices = vec![Vec::new(); sources.len()];
+        for handle in handles {
+            for (number, index) in handle.join().expect("indexing thread panicked") { indices[number] = index; }
+        }
+        indices
+    });
+
+    let mut samples: Vec<u64> = indices.iter().flat_map(|index| index[1 ..].iter().map(|&(_, value)| value)).collect();
+    samples.sort_unstable();
+
+    let parts = std::cmp::max(4 * threads, samples.len() * stride / PART_VALUES);
+    let mut bounds = vec![0u64];
+    for part in 1 .. parts {
+        if let Some(&value) = samples.get(part * samples.len() / parts) {
+            if bounds.last() < Some(&value) { bounds.push(value); }
+        }
+    }
+    let ranges: Vec<RangeInclusive<u64>> = (0 .. bounds.len()).map(|index| {
+        bounds[index] ..= bounds.get(index + 1).map(|&bound| bound - 1).unwrap_or(u64::MAX)
+    }).collect();
+
+    std::thread::scope(|scope| {
+        let receivers: Vec<_> = (0 .. threads).map(|worker| {
+            let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+            let (ranges, indices) = (&ranges, &indices);
+            scope.spawn(move || {
+                for range in ranges.iter().skip(worker).step_by(threads) {
+                    // a closed channel means the writer failed, and nobody wants the rest.
+                    if sender.send(merge_part(sources, indices, range)).is_err() { break; }
                 }
+            });
+            receiver
+        }).collect();
+
+        let mut prev = 0u64;
+        for part in 0 .. ranges.len() {
+            if let Some((first, last, bytes)) = receivers[part % threads].recv().expect("merge thread panicked") {
+                try_encode(writer, first - prev)?;
+                writer.write_all(&bytes[..])?;
+                prev = last;
             }
         }
+        Ok(())
+    })
+}
 
-        if arg_min < iterators.len() {
-            values[arg_min] = iterators[arg_min].next();
-            if let Some(val) = values[arg_min] {
-                assert!(val > val_min);
-            }
-            assert!(val_old <= val_min);
-            val_old = val_min;
-            output(val_min);
+// merges the values of `sources` within `range`, returning the first and last values merged and the
+// deltas following the first, or `None` if no source has values in `range`.
+fn merge_part(sources: &[&[u8]], indices: &[Vec<(usize, u64)>], range: &RangeInclusive<u64>) -> Option<(u64, u64, Vec<u8>)> {
+    let (lower, upper) = (*range.start(), *range.end());
+    let iterators = sources.iter().zip(indices.iter()).map(|(&source, index)| {
+        let (offset, current) = index[index.partition_point(|&(_, value)| value < lower).saturating_sub(1)];
+        Decoder { reader: &source[offset ..], current }.skip_while(move |&x| x < lower).take_while(move |&x| x <= upper)
+    }).collect();
+
+    let mut bytes = Vec::new();
+    let mut ends: Option<(u64, u64)> = None;
+    merge(iterators, |next| {
+        match ends {
+            None => ends = Some((next, next)),
+            Some((first, last)) => if last < next {
+                encode(&mut bytes, next - last);
+                ends = Some((first, next));
+            },
         }
-        else {
-            done = true;
+    });
+    ends.map(|(first, last)| (first, last, bytes))
+}
+
+#[test]
+fn test_merge() {
+    let sequences: [&[u64]; 4] = [&[1, 4, 9, 16], &[2, 3, 5, 7, 11], &[], &[4, 8, 15, 16, 23, 42]];
+    let mut expected: Vec<u64> = sequences.iter().flat_map(|s| s.iter().cloned()).collect();
+    expected.sort();
+
+    let mut merged = Vec::new();
+    merge(sequences.iter().map(|s| s.iter().cloned()).collect(), |x| merged.push(x));
+    assert_eq!(merged, expected);
+
+    // merge_parallel also drops the duplicates, and must agree however the work is cut up.
+    expected.dedup();
+    let streams: Vec<Vec<u8>> = sequences.iter().map(|sequence| {
+        let mut bytes = Vec::new();
+        let mut prev = 0;
+        for &value in sequence.iter() { encode(&mut bytes, value - prev); prev = value; }
+        bytes
+    }).collect();
+    let streams: Vec<&[u8]> = streams.iter().map(|stream| &stream[..]).collect();
+    for threads in 1 .. 4 {
+        for stride in 1 .. 4 {
+            let mut bytes = Vec::new();
+            merge_parallel_with(&streams, threads, stride, &mut bytes).unwrap();
+            assert_eq!(Decoder::new(&bytes[..]).collect::<Vec<_>>(), expected, "{} threads, stride {}", threads, stride);
         }
     }
-
-    // confirm that we haven't left anything behind
-    assert!(!values.iter().any(|x|x.is_some()));
+    assert_eq!(skip_index(streams[1], 2), vec![(0, 0), (2, 3), (4, 7)]);
 }
 
 // algorithm drawn in large part from http://en.wikipedia.org/wiki/Hilbert_curve
@@ -195,9 +488,56 @@ impl BytewiseCached {
     pub fn detangle(&mut self, tangle: u64) -> (u32, u32) {
         let (mut x_byte, mut y_byte) = unsafe { *self.hilbert.detangle.get_unchecked(tangle as u16 as usize) };
 
-        // validate self.prev_rot, self.prev_out
-        if self.prev_hi != (tangle >> 16) {
-            self.prev_hi = tangle >> 16;
+        self.orient(tangle >> 16);
+
+        if self.prev_rot.1 {
+            x_byte = 255 - x_byte;
+            y_byte = 255 - y_byte;
+        }
+        if self.prev_rot.0 {
+            let temp = x_byte; x_byte = y_byte; y_byte = temp;
+        }
+
+        return (self.prev_out.0 + x_byte as u32, self.prev_out.1 + y_byte as u32);
+    }
+
+    /// Detangles `tangles` into `xs` and `ys`, which must have the same length.
+    ///
+    /// Consecutive indices sharing all but their low 16 bits share one rotation state, so each such
+    /// run is decoded by table lookups and mask arithmetic alone, without branches, which lets the
+    /// compiler unroll and vectorize the loop.
+    pub fn detangle_batch(&mut self, tangles: &[u64], xs: &mut [u32], ys: &mut [u32]) {
+        assert!(tangles.len() == xs.len() && tangles.len() == ys.len(), "batch lengths differ");
+
+        let mut start = 0;
+        while start < tangles.len() {
+            let high = tangles[start] >> 16;
+            let mut end = start + 1;
+            while end < tangles.len() && (tangles[end] >> 16) == high { end += 1; }
+
+            self.orient(high);
+            let flip = if self.prev_rot.1 { 0xFFu32 } else { 0 };
+            let swap = if self.prev_rot.0 { 0xFFFFFFFFu32 } else { 0 };
+            let (base_x, base_y) = self.prev_out;
+            let table = &self.hilbert.detangle[..];
+
+            for ((&tangle, x), y) in tangles[start .. end].iter().zip(xs[start .. end].iter_mut()).zip(ys[start .. end].iter_mut()) {
+                let (x_byte, y_byte) = unsafe { *table.get_unchecked(tangle as u16 as usize) };
+                let x_byte = x_byte as u32 ^ flip;
+                let y_byte = y_byte as u32 ^ flip;
+                *x = base_x + ((x_byte & !swap) | (y_byte & swap));
+                *y = base_y + ((y_byte & !swap) | (x_byte & swap));
+            }
+
+            start = end;
+        }
+    }
+
+    // validate self.prev_rot, self.prev_out for indices whose bits above the lowest 16 are `high`.
+    #[inline(always)]
+    fn orient(&mut self, high: u64) {
+        if self.prev_hi != high {
+            self.prev_hi = high;
 
             // detangle with a bit set to see what happens to it
             let low = 255; //self.hilbert.entangle((0xF, 0)) as u16;
@@ -213,18 +553,8 @@ impl BytewiseCached {
             };
             self.prev_out = (x & 0xFFFFFF00, y & 0xFFFFFF00);
         }
-
-
-        if self.prev_rot.1 {
-            x_byte = 255 - x_byte;
-            y_byte = 255 - y_byte;
-        }
-        if self.prev_rot.0 {
-            let temp = x_byte; x_byte = y_byte; y_byte = temp;
-        }
-
-        return (self.prev_out.0 + x_byte as u32, self.prev_out.1 + y_byte as u32);
     }
+
     pub fn new() -> BytewiseCached {
         let mut result = BytewiseCached {
             hilbert: BytewiseHilbert::new(),
@@ -238,6 +568,34 @@ impl BytewiseCached {
     }
 }
 
+#[test]
+fn test_detangle_batch() {
+    use generators::XorShift;
+
+    // sorted indices, clustered so that runs share their upper bits, with some far-flung ones.
+    let mut rng = XorShift::new(0);
+    let mut tangles = Vec::new();
+    for _ in 0 .. 64 {
+        let high = rng.next_u64() & !0xFFFF;
+        for _ in 0 .. rng.below(100) { tangles.push(high + rng.below(1 << 16)); }
+    }
+    tangles.sort();
+
+    let mut scalar = BytewiseCached::new();
+    let expected: Vec<(u32, u32)> = tangles.iter().map(|&tangle| scalar.detangle(tangle)).collect();
+
+    let mut batch = BytewiseCached::new();
+    let mut xs = vec![0u32; tangles.len()];
+    let mut ys = vec![0u32; tangles.len()];
+    for start in (0 .. tangles.len()).step_by(37) {
+        let end = ::std::cmp::min(start + 37, tangles.len());
+        batch.detangle_batch(&tangles[start .. end], &mut xs[start .. end], &mut ys[start .. end]);
+    }
+    let found: Vec<(u32, u32)> = xs.into_iter().zip(ys).collect();
+
+    assert_eq!(found, expected);
+}
+
 pub struct BytewiseHilbert {
     entangle: Vec<u16>,         // entangle[x_byte << 16 + y_byte] -> tangle
     detangle: Vec<(u8, u8)>,    // detangle[tangle] -> (x_byte, y_byte)
@@ -310,6 +668,151 @@ impl BytewiseHilbert {
     }
 }
 
+/// A Hilbert curve over `dims` coordinates of `bits` bits each, with indices of up to 128 bits.
+///
+/// Uses Skilling's transpose-based algorithm ("Programming the Hilbert curve", 2004), so it handles
+/// any width and number of dimensions, e.g. 16, 32, or 64-bit pairs, or `(src, dst, time)` triples.
+/// In two dimensions with 32 bits it visits pairs in the same order as `BytewiseHilbert`, and with
+/// fewer bits in the same order as `BytewiseHilbert` visits pairs below `1 << bits`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hilbert {
+    dims: usize,
+    bits: usize,
+}
+
+impl Hilbert {
+    pub fn new(dims: usize, bits: usize) -> Hilbert {
+        assert!(dims > 0 && bits > 0, "hilbert curve needs at least one dimension and one bit");
+        assert!(bits <= 64, "hilbert curve coordinates are at most 64 bits: {}", bits);
+        assert!(dims * bits <= 128, "hilbert curve indices are at most 128 bits: {} x {}", dims, bits);
+        Hilbert { dims, bits }
+    }
+
+    pub fn dims(&self) -> usize { self.dims }
+    pub fn bits(&self) -> usize { self.bits }
+
+    /// The index of `coords`, which must have `dims` entries each less than `1 << bits`.
+    pub fn entangle(&self, coords: &[u64]) -> u128 {
+        assert_eq!(coords.len(), self.dims);
+        let mut x = [0u64; 128];
+        let x = &mut x[.. self.dims];
+        x.copy_from_slice(coords);
+        debug_assert!(self.bits == 64 || x.iter().all(|&c| c >> self.bits == 0));
+
+        // undo the excess work of the inverse transform, from the top bit down.
+        for bit in (1 .. self.bits).rev() {
+            let q = 1u64 << bit;
+            let p = q - 1;
+            for i in 0 .. self.dims {
+                if x[i] & q != 0 { x[0] ^= p; }
+                else {
+                    let t = (x[0] ^ x[i]) & p;
+                    x[0] ^= t;
+                    x[i] ^= t;
+                }
+            }
+        }
+
+        // gray encode.
+        for i in 1 .. self.dims { x[i] ^= x[i - 1]; }
+        let mut t = 0u64;
+        for bit in (1 .. self.bits).rev() {
+            if x[self.dims - 1] & (1 << bit) != 0 { t ^= (1 << bit) - 1; }
+        }
+        for c in x.iter_mut() { *c ^= t; }
+
+        // interleave the transposed coordinates, the first taking the highest bit of each group.
+        let mut result = 0u128;
+        for bit in (0 .. self.bits).rev() {
+            for &c in x.iter() {
+                result = (result << 1) | ((c >> bit) & 1) as u128;
+            }
+        }
+        result
+    }
+
+    /// Writes the coordinates whose index is `tangle` into `coords`, which must have `dims` entries.
+    pub fn detangle(&self, tangle: u128, coords: &mut [u64]) {
+        assert_eq!(coords.len(), self.dims);
+        let x = coords;
+        for c in x.iter_mut() { *c = 0; }
+        for bit in 0 .. self.bits {
+            for (i, c) in x.iter_mut().enumerate() {
+                let position = bit * self.dims + (self.dims - 1 - i);
+                *c |= (((tangle >> position) & 1) as u64) << bit;
+            }
+        }
+
+        // gray decode.
+        let t = x[self.dims - 1] >> 1;
+        for i in (1 .. self.dims).rev() { x[i] ^= x[i - 1]; }
+        x[0] ^= t;
+
+        // undo the excess work, from the second bit up.
+        for bit in 1 .. self.bits {
+            let q = 1u64 << bit;
+            let p = q - 1;
+            for i in (0 .. self.dims).rev() {
+                if x[i] & q != 0 { x[0] ^= p; }
+                else {
+                    let t = (x[0] ^ x[i]) & p;
+                    x[0] ^= t;
+                    x[i] ^= t;
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_hilbert() {
+    use generators::XorShift;
+    let mut rng = XorShift::new(0);
+
+    // two dimensions agree with the reference implementation, at full and reduced widths.
+    let curve = Hilbert::new(2, 32);
+    let narrow = Hilbert::new(2, 16);
+    let mut coords = [0u64; 2];
+    for _ in 0 .. 10000 {
+        let (x, y) = (rng.next_u64() as u32, rng.next_u64() as u32);
+        assert_eq!(curve.entangle(&[x as u64, y as u64]), bit_entangle((x, y)) as u128);
+        curve.detangle(bit_entangle((x, y)) as u128, &mut coords);
+        assert_eq!(coords, [x as u64, y as u64]);
+
+        let (x, y) = (x >> 16, y >> 16);
+        assert_eq!(narrow.entangle(&[x as u64, y as u64]), bit_entangle((x, y)) as u128);
+    }
+
+    // wider and higher-dimensional curves round-trip.
+    for &(dims, bits) in [(2, 64), (3, 16), (3, 32), (3, 42), (4, 32), (5, 7)].iter() {
+        let curve = Hilbert::new(dims, bits);
+        let mask = if bits == 64 { u64::MAX } else { (1 << bits) - 1 };
+        let mut found = vec![0u64; dims];
+        for _ in 0 .. 1000 {
+            let coords: Vec<u64> = (0 .. dims).map(|_| rng.next_u64() & mask).collect();
+            curve.detangle(curve.entangle(&coords), &mut found);
+            assert_eq!(found, coords);
+        }
+    }
+
+    // consecutive indices of a small 3d curve are unit steps apart, and every point is visited.
+    let curve = Hilbert::new(3, 4);
+    let mut prev = [0u64; 3];
+    let mut next = [0u64; 3];
+    let mut seen = vec![false; 1 << 12];
+    curve.detangle(0, &mut prev);
+    assert_eq!(prev, [0, 0, 0]);
+    for tangle in 1 .. (1 << 12) {
+        curve.detangle(tangle, &mut next);
+        let distance: u64 = prev.iter().zip(next.iter()).map(|(&a, &b)| a.abs_diff(b)).sum();
+        assert_eq!(distance, 1);
+        assert_eq!(curve.entangle(&next), tangle);
+        seen[((next[0] << 8) | (next[1] << 4) | next[2]) as usize] = true;
+        prev = next;
+    }
+    assert!(seen[1..].iter().all(|&s| s));
+}
+
 fn bit_entangle(mut pair: (u32, u32)) -> u64 {
     let mut result = 0u64;
     for log_s_rev in 0..32 {
@@ -340,7 +843,8 @@ fn bit_detangle(tangle: u64) -> (u32, u32) {
 fn bit_rotate(logn: usize, pair: (u32, u32), rx: u32, ry: u32) -> (u32, u32) {
     if ry == 0 {
         if rx != 0 {
-            ((1 << logn) - pair.1 - 1, (1 << logn) - pair.0 - 1)
+            // bits above `logn` may be set while entangling; they are masked off later, so wrap.
+            ((1u32 << logn).wrapping_sub(pair.1).wrapping_sub(1), (1u32 << logn).wrapping_sub(pair.0).wrapping_sub(1))
         }
         else { (pair.1, pair.0) }
     }
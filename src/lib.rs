@@ -1,9 +1,14 @@
 #![allow(non_snake_case)]
 
 extern crate lz4;
-extern crate memmap;
+extern crate memmap2;
 extern crate byteorder;
 
 pub mod typedrw;
 pub mod hilbert_curve;
+pub mod zorder;
 pub mod graph_iterator;
+pub mod report;
+pub mod generators;
+pub mod compression;
+pub mod writers;
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// How a binary should present its results.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// Human-readable progress and results, as the binaries have always printed.
+    Text,
+    /// A single JSON record on stdout once the run completes, and nothing else.
+    Json,
+}
+
+impl Format {
+    /// Removes `--format <text|json>` from `args` if present, defaulting to `Text`.
+    pub fn extract(args: &mut Vec<String>) -> Format {
+        match args.iter().position(|arg| arg == "--format") {
+            Some(index) => {
+                assert!(index + 1 < args.len(), "--format requires an argument");
+                let format = match args[index + 1].as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => panic!("unrecognized format: {:?}", other),
+                };
+                args.drain(index .. index + 2);
+                format
+            },
+            None => Format::Text,
+        }
+    }
+}
+
+/// A JSON value, as needed for run records.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Int(u64),
+    Float(f64),
+    Str(String),
+    List(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl From<u64> for Value { fn from(x: u64) -> Value { Value::Int(x) } }
+impl From<u32> for Value { fn from(x: u32) -> Value { Value::Int(x as u64) } }
+impl From<usize> for Value { fn from(x: usize) -> Value { Value::Int(x as u64) } }
+impl From<f64> for Value { fn from(x: f64) -> Value { Value::Float(x) } }
+impl From<f32> for Value { fn from(x: f32) -> Value { Value::Float(x as f64) } }
+impl<'a> From<&'a str> for Value { fn from(x: &'a str) -> Value { Value::Str(x.to_owned()) } }
+impl From<String> for Value { fn from(x: String) -> Value { Value::Str(x) } }
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(x: Vec<T>) -> Value { Value::List(x.into_iter().map(|y| y.into()).collect()) }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Int(x) => write!(f, "{}", x),
+            // JSON has no representation for NaN or infinities.
+            Value::Float(x) => if x.is_finite() { write!(f, "{:?}", x) } else { write!(f, "null") },
+            Value::Str(ref x) => {
+                write!(f, "\"")?;
+                for c in x.chars() {
+                    match c {
+                        '"' => write!(f, "\\\"")?,
+                        '\\' => write!(f, "\\\\")?,
+                        '\n' => write!(f, "\\n")?,
+                        '\t' => write!(f, "\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            },
+            Value::List(ref xs) => {
+                write!(f, "[")?;
+                for (index, x) in xs.iter().enumerate() {
+                    if index > 0 { write!(f, ",")?; }
+                    write!(f, "{}", x)?;
+                }
+                write!(f, "]")
+            },
+            Value::Object(ref xs) => {
+                write!(f, "{{")?;
+                for (index, (key, x)) in xs.iter().enumerate() {
+                    if index > 0 { write!(f, ",")?; }
+                    write!(f, "{}:{}", Value::Str(key.clone()), x)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+/// Collects what a run did: which algorithm on which graph, how long each iteration took, and
+/// what it found. In `Json` format `finish` prints everything as one record; in `Text` format
+/// binaries print as they go and `finish` prints nothing.
+pub struct Report {
+    format:     Format,
+    fields:     Vec<(String, Value)>,
+    results:    Vec<(String, Value)>,
+    iterations: Vec<f64>,
+    timer:      Instant,
+    last:       Duration,
+}
+
+impl Report {
+    pub fn new(format: Format, algorithm: &str, mode: &str, graph: &str) -> Report {
+        Report {
+            format,
+            fields: vec![
+                ("algorithm".to_owned(), algorithm.into()),
+                ("mode".to_owned(), mode.into()),
+                ("graph".to_owned(), graph.into()),
+            ],
+            results: Vec::new(),
+            iterations: Vec::new(),
+            timer: Instant::now(),
+            last: Duration::from_secs(0),
+        }
+    }
+
+    /// True if the binary should print its usual human-readable output.
+    pub fn is_text(&self) -> bool { self.format == Format::Text }
+
+    /// Sets a top-level field of the record, such as `nodes` or `edges`.
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) {
+        self.fields.retain(|(k, _)| k != key);
+        self.fields.push((key.to_owned(), value.into()));
+    }
+
+    /// Records the end of an iteration, timed from the end of the previous one (or the report's creation).
+    pub fn iteration(&mut self) {
+        let elapsed = self.timer.elapsed();
+        self.iterations.push((elapsed - self.last).as_secs_f64());
+        self.last = elapsed;
+    }
+
+    /// Sets a field of the record's `result` object.
+    pub fn result<V: Into<Value>>(&mut self, key: &str, value: V) {
+        self.results.retain(|(k, _)| k != key);
+        self.results.push((key.to_owned(), value.into()));
+    }
+
+    /// Prints the record, if the format calls for it.
+    pub fn finish(&self) {
+        if self.format == Format::Json {
+            let mut record = self.fields.clone();
+            record.push(("elapsed".to_owned(), self.timer.elapsed().as_secs_f64().into()));
+            record.push(("iterations".to_owned(), self.iterations.clone().into()));
+            if let Some(kilobytes) = peak_rss_kb() {
+                record.push(("peak_rss_kb".to_owned(), kilobytes.into()));
+            }
+            record.push(("result".to_owned(), Value::Object(self.results.clone())));
+            println!("{}", Value::Object(record));
+        }
+    }
+}
+
+/// The peak resident set size of this process in kilobytes, where the platform reports it (Linux).
+pub fn peak_rss_kb() -> Option<u64> {
+    let mut status = String::new();
+    File::open("/proc/self/status").ok()?.read_to_string(&mut status).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line["VmHWM:".len() ..].trim().trim_end_matches("kB").trim().parse().ok()
+}
+
+/// Ends a binary with a message, rather than a panic, when it cannot open or write its files.
+pub trait OrExit<T> {
+    /// The value, or else prints `<binary>: <error>` to stderr and exits with status 1.
+    ///
+    /// `AlreadyExists` errors, which `writers::check_outputs` returns, also get a note about `--force`.
+    fn or_exit(self, binary: &str) -> T;
+}
+
+impl<T> OrExit<T> for io::Result<T> {
+    fn or_exit(self, binary: &str) -> T {
+        self.unwrap_or_else(|error| {
+            eprintln!("{}: {}", binary, error);
+            if error.kind() == io::ErrorKind::AlreadyExists { eprintln!("NOTE: pass --force to overwrite it."); }
+            std::process::exit(1)
+        })
+    }
+}
@@ -0,0 +1,390 @@
+//! Writers producing the vertex, Hilbert, and compressed formats from streams of edges.
+//!
+//! Each writer checks that edges arrive in the order its format needs, buffers its output, and
+//! writes every file under a temporary name, renaming it into place only once `finish` succeeds,
+//! so readers never see a partially written file. A writer dropped without `finish` removes its
+//! temporary files and leaves any previous graph untouched.
+//!
+//! Only each file is replaced atomically, not a graph's files together: should `finish` fail
+//! between renames, the graph is left with some new files and some old ones. The data files are
+//! renamed before the files counting their edges, and the readers check the counts, so such a
+//! graph is refused when opened rather than misread.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+
+use byteorder::{WriteBytesExt, LittleEndian};
+use compression::{Output, LZ4_EXTENSION};
+use hilbert_curve::{BytewiseHilbert, DEFAULT_LOW_BITS, try_encode, split_header, split_record_bytes};
+use typedrw::annotate;
+
+/// A file written under a temporary name in the same directory, and renamed into place by `commit`.
+pub struct AtomicFile {
+    path:       String,
+    other:      String,     // the same file in the other compression, removed by `commit`
+    temp:       String,
+    writer:     Option<Output<BufWriter<File>>>,
+}
+
+impl AtomicFile {
+    /// Starts writing `path`, or if `lz4` is set, LZ4-compressing what is written to `path` with
+    /// `.lz4` appended, as readers recognize compressed files by name.
+    pub fn create(path: &str, lz4: bool) -> io::Result<AtomicFile> {
+        let base = path.strip_suffix(LZ4_EXTENSION).unwrap_or(path);
+        if !lz4 && base != path {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{}: only compressed files are named .lz4", path)));
+        }
+        let compressed = format!("{}{}", base, LZ4_EXTENSION);
+        let (path, other) = if lz4 { (compressed, base.to_owned()) } else { (base.to_owned(), compressed) };
+
+        let temp = format!("{}.tmp-{}", path, ::std::process::id());
+        let file = File::create(&temp).map_err(|error| annotate(error, &temp))?;
+        let mut result = AtomicFile { path, other, temp, writer: None };
+        result.writer = Some(Output::new(BufWriter::new(file), lz4)?);
+        Ok(result)
+    }
+
+    /// The path the file will have once committed.
+    pub fn path(&self) -> &str { &self.path }
+
+    /// Flushes the file to disk and renames it into place, replacing any file already there, and
+    /// removes any copy in the other compression, which readers would otherwise find alongside it.
+    pub fn commit(mut self) -> io::Result<()> {
+        let result = self.writer.take().expect("writer already taken").finish()
+            .and_then(|_| File::open(&self.temp)?.sync_all())
+            .and_then(|_| fs::rename(&self.temp, &self.path))
+            .map_err(|error| annotate(error, &self.path));
+        if result.is_err() { let _ = fs::remove_file(&self.temp); }
+        result?;
+        match fs::remove_file(&self.other) {
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            other => other.map_err(|error| annotate(error, &self.other)),
+        }
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.as_mut().expect("writer already taken").write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.as_mut().expect("writer already taken").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// Fails with `AlreadyExists`, naming the first of `paths` that exists, compressed or not, unless
+/// `force` is set.
+///
+/// Writers replace existing files when they finish; tools call this first so that they refuse to
+/// replace an existing graph before doing any work.
+pub fn check_outputs(paths: &[String], force: bool) -> io::Result<()> {
+    if !force {
+        for path in paths {
+            let base = path.strip_suffix(LZ4_EXTENSION).unwrap_or(path);
+            for path in &[base.to_owned(), format!("{}{}", base, LZ4_EXTENSION)] {
+                if fs::symlink_metadata(path).is_ok() {
+                    return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path)));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn out_of_order(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Writes `<prefix>.nodes` and `<prefix>.edges`, as read by `NodesEdgesMemMapper`.
+///
+/// Edges from `new` must arrive grouped by source, in increasing order of source. Edges from
+/// `unsorted` may arrive in any order, and a new node record starts whenever the source changes.
+pub struct VertexGraphWriter {
+    nodes:      AtomicFile,
+    edges:      AtomicFile,
+    sorted:     bool,
+    source:     Option<u32>,
+    count:      u32,
+}
+
+impl VertexGraphWriter {
+    pub fn new(prefix: &str, lz4: bool) -> io::Result<VertexGraphWriter> {
+        Ok(VertexGraphWriter {
+            nodes: AtomicFile::create(&format!("{}.nodes", prefix), lz4)?,
+            edges: AtomicFile::create(&format!("{}.edges", prefix), lz4)?,
+            sorted: true,
+            source: None,
+            count: 0,
+        })
+    }
+
+    /// As `new`, but accepting edges in any order.
+    ///
+    /// The files support `map_nodes` and `map_edges`, but `NodesEdgesMemMapper::index` needs sorted input.
+    pub fn unsorted(prefix: &str, lz4: bool) -> io::Result<VertexGraphWriter> {
+        let mut writer = VertexGraphWriter::new(prefix, lz4)?;
+        writer.sorted = false;
+        Ok(writer)
+    }
+
+    pub fn push(&mut self, src: u32, dst: u32) -> io::Result<()> {
+        if self.source != Some(src) {
+            if self.sorted && self.source > Some(src) {
+                return Err(out_of_order(format!("edges not grouped by increasing source: {} after {:?}", src, self.source)));
+            }
+            self.end_source()?;
+            self.source = Some(src);
+        }
+        if self.count == u32::MAX {
+            return Err(out_of_order(format!("too many edges from source {}", src)));
+        }
+
+        self.edges.write_u32::<LittleEndian>(dst)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    fn end_source(&mut self) -> io::Result<()> {
+        if let Some(source) = self.source {
+            self.nodes.write_u32::<LittleEndian>(source)?;
+            self.nodes.write_u32::<LittleEndian>(self.count)?;
+            self.count = 0;
+        }
+        Ok(())
+    }
+
+    /// Writes the last source's record and renames both files into place.
+    ///
+    /// The edges file is renamed first, so a reader seeing a new `.nodes` file sees the edges it
+    /// counts; `NodesEdgesMemMapper::new` refuses files whose counts disagree.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end_source()?;
+        self.edges.commit()?;
+        self.nodes.commit()
+    }
+}
+
+/// Writes `<prefix>.upper` and `<prefix>.lower`, as read by `UpperLowerMemMapper`.
+///
+/// Edges must arrive in order of their Hilbert indices, as `convert_to_hilbert` produces them;
+/// duplicate edges are kept. Splits other than `DEFAULT_LOW_BITS` are recorded in a header at the
+/// start of the upper file, as written by `split_header`.
+pub struct HilbertGraphWriter {
+    upper:      AtomicFile,
+    lower:      AtomicFile,
+    low_bits:   u32,
+    hilbert:    BytewiseHilbert,
+    previous:   Option<u64>,
+    block:      (u32, u32),
+    count:      u32,
+}
+
+impl HilbertGraphWriter {
+    pub fn new(prefix: &str, lz4: bool) -> io::Result<HilbertGraphWriter> {
+        HilbertGraphWriter::with_split(prefix, DEFAULT_LOW_BITS, lz4)
+    }
+
+    /// As `new`, but with blocks of `1 << low_bits` by `1 << low_bits` vertices, for `low_bits` from 8 to 24.
+    pub fn with_split(prefix: &str, low_bits: u32, lz4: bool) -> io::Result<HilbertGraphWriter> {
+        if !(8 ..= 24).contains(&low_bits) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("unsupported split: {} low bits", low_bits)));
+        }
+        let mut upper = AtomicFile::create(&format!("{}.upper", prefix), lz4)?;
+        if low_bits != DEFAULT_LOW_BITS {
+            upper.write_all(&split_header(low_bits))?;
+        }
+        Ok(HilbertGraphWriter {
+            upper,
+            lower: AtomicFile::create(&format!("{}.lower", prefix), lz4)?,
+            low_bits,
+            hilbert: BytewiseHilbert::new(),
+            previous: None,
+            block: (0, 0),
+            count: 0,
+        })
+    }
+
+    pub fn push(&mut self, src: u32, dst: u32) -> io::Result<()> {
+        let tangle = self.hilbert.entangle((src, dst));
+        if self.previous > Some(tangle) {
+            return Err(out_of_order(format!("edge ({}, {}) out of Hilbert order", src, dst)));
+        }
+
+        let block = (src >> self.low_bits, dst >> self.low_bits);
+        if self.previous.map(|previous| previous >> (2 * self.low_bits)) != Some(tangle >> (2 * self.low_bits)) {
+            self.end_block()?;
+            self.block = block;
+        }
+        if self.count == u32::MAX {
+            return Err(out_of_order(format!("too many edges in block {:?}", block)));
+        }
+        self.previous = Some(tangle);
+
+        let mask = (1u32 << self.low_bits) - 1;
+        if self.low_bits == DEFAULT_LOW_BITS {
+            self.lower.write_u16::<LittleEndian>(src as u16)?;
+            self.lower.write_u16::<LittleEndian>(dst as u16)?;
+        }
+        else {
+            let packed = (((src & mask) as u64) << self.low_bits) | (dst & mask) as u64;
+            self.lower.write_uint::<LittleEndian>(packed, split_record_bytes(self.low_bits).1 as usize)?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn end_block(&mut self) -> io::Result<()> {
+        if self.count > 0 {
+            if self.low_bits == DEFAULT_LOW_BITS {
+                self.upper.write_u16::<LittleEndian>(self.block.0 as u16)?;
+                self.upper.write_u16::<LittleEndian>(self.block.1 as u16)?;
+            }
+            else {
+                self.upper.write_u32::<LittleEndian>(self.block.0)?;
+                self.upper.write_u32::<LittleEndian>(self.block.1)?;
+            }
+            self.upper.write_u32::<LittleEndian>(self.count)?;
+            self.count = 0;
+        }
+        Ok(())
+    }
+
+    /// Writes the last block's record and renames both files into place.
+    ///
+    /// The lower file goes first; should the upper rename fail, `UpperLowerMemMapper::from_files`
+    /// notices that the old blocks do not count the new lower file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end_block()?;
+        self.lower.commit()?;
+        self.upper.commit()
+    }
+}
+
+/// Writes a delta-compressed file, as read by `DeltaCompressedReaderMapper` and `DeltaCompressedSliceMapper`.
+///
+/// Edges must arrive in strictly increasing order of their Hilbert indices, and cannot include
+/// `(0, 0)`, whose Hilbert index is zero.
+pub struct CompressedGraphWriter {
+    file:       AtomicFile,
+    hilbert:    BytewiseHilbert,
+    previous:   u64,
+}
+
+impl CompressedGraphWriter {
+    pub fn new(path: &str, lz4: bool) -> io::Result<CompressedGraphWriter> {
+        Ok(CompressedGraphWriter {
+            file: AtomicFile::create(path, lz4)?,
+            hilbert: BytewiseHilbert::new(),
+            previous: 0,
+        })
+    }
+
+    pub fn push(&mut self, src: u32, dst: u32) -> io::Result<()> {
+        let tangle = self.hilbert.entangle((src, dst));
+        self.push_index(tangle)
+    }
+
+    /// Appends the edge with Hilbert index `tangle`, as produced by `to_hilbert`.
+    pub fn push_index(&mut self, tangle: u64) -> io::Result<()> {
+        if tangle <= self.previous {
+            return Err(out_of_order(format!("Hilbert index {} not above previous index {}", tangle, self.previous)));
+        }
+        try_encode(&mut self.file, tangle - self.previous)?;
+        self.previous = tangle;
+        Ok(())
+    }
+
+    /// Renames the file into place.
+    pub fn finish(self) -> io::Result<()> {
+        self.file.commit()
+    }
+}
+
+#[test]
+fn test_writers() {
+    use graph_iterator::{EdgeMapper, NodesEdgesMemMapper, UpperLowerMemMapper};
+    use hilbert_curve::{to_hilbert, Decoder};
+
+    let directory = ::std::env::temp_dir().join(format!("cost-writers-{}", ::std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let prefix = directory.join("graph").to_string_lossy().into_owned();
+    fn sorted_edges<G: EdgeMapper>(graph: &G) -> Vec<(u32, u32)> {
+        let mut edges = Vec::new();
+        graph.map_edges(|x, y| edges.push((x, y)));
+        edges.sort();
+        edges
+    }
+
+    let edges = vec![(0, 1), (0, 70000), (1, 0), (5, 5), (5, 6), (70000, 3), (70000, 3)];
+
+    let mut vertex = VertexGraphWriter::new(&prefix, false).unwrap();
+    for &(src, dst) in &edges { vertex.push(src, dst).unwrap(); }
+    assert!(fs::metadata(format!("{}.nodes", prefix)).is_err(), "nodes visible before finish");
+    vertex.finish().unwrap();
+    let graph = NodesEdgesMemMapper::new(&prefix).unwrap();
+    assert_eq!(sorted_edges(&graph), edges);
+
+    let mut tangles = Vec::new();
+    to_hilbert(&edges, |tangle| tangles.push(tangle));
+    let hilbert = BytewiseHilbert::new();
+    for &low_bits in &[DEFAULT_LOW_BITS, 9] {
+        let mut writer = HilbertGraphWriter::with_split(&prefix, low_bits, low_bits == 9).unwrap();
+        for &tangle in &tangles { writer.push(hilbert.detangle(tangle).0, hilbert.detangle(tangle).1).unwrap(); }
+        writer.finish().unwrap();
+        let graph = UpperLowerMemMapper::new(&prefix).unwrap();
+        assert_eq!(sorted_edges(&graph), edges, "{} low bits", low_bits);
+        // compressed files are named .lz4, and replace uncompressed ones.
+        assert_eq!(fs::metadata(format!("{}.upper", prefix)).is_ok(), low_bits == DEFAULT_LOW_BITS);
+        assert_eq!(fs::metadata(format!("{}.upper.lz4", prefix)).is_ok(), low_bits == 9);
+    }
+
+    let path = format!("{}.compressed", prefix);
+    let mut writer = CompressedGraphWriter::new(&path, false).unwrap();
+    tangles.dedup();
+    for &tangle in &tangles { writer.push_index(tangle).unwrap(); }
+    writer.finish().unwrap();
+    assert_eq!(Decoder::new(File::open(&path).unwrap()).collect::<Vec<_>>(), tangles);
+
+    // unsorted writers start a new record whenever the source changes.
+    let mut vertex = VertexGraphWriter::unsorted(&prefix, false).unwrap();
+    for &(src, dst) in edges.iter().rev() { vertex.push(src, dst).unwrap(); }
+    vertex.finish().unwrap();
+    let graph = NodesEdgesMemMapper::new(&prefix).unwrap();
+    let mut nodes = Vec::new();
+    graph.map_nodes(|node, edges| nodes.push((node, edges.len())));
+    assert_eq!(nodes, vec![(70000, 2), (5, 2), (1, 1), (0, 2)]);
+    assert_eq!(sorted_edges(&graph), edges);
+    let index = graph.index(70001).unwrap();
+    assert_eq!(index.neighbors(5), &[6, 5]);
+    assert_eq!(index.neighbors(2), &[] as &[u32]);
+    assert!(graph.index(70000).is_err());
+
+    // a source whose edges are not together has no single adjacency list.
+    let mut vertex = VertexGraphWriter::unsorted(&prefix, false).unwrap();
+    for &(src, dst) in &[(1, 0), (2, 0), (1, 1)] { vertex.push(src, dst).unwrap(); }
+    vertex.finish().unwrap();
+    let graph = NodesEdgesMemMapper::new(&prefix).unwrap();
+    assert_eq!(graph.index(3).err().map(|error| error.kind()), Some(io::ErrorKind::InvalidData));
+
+    // out of order edges are refused, and abandoned writers leave no files behind.
+    let mut vertex = VertexGraphWriter::new(&format!("{}-unsorted", prefix), false).unwrap();
+    vertex.push(3, 0).unwrap();
+    assert_eq!(vertex.push(2, 0).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    let mut hilbert = HilbertGraphWriter::new(&format!("{}-unsorted", prefix), false).unwrap();
+    hilbert.push(70000, 3).unwrap();
+    assert!(hilbert.push(0, 1).is_err());
+    let mut compressed = CompressedGraphWriter::new(&format!("{}-unsorted", prefix), false).unwrap();
+    assert!(compressed.push(0, 0).is_err());
+    drop((vertex, hilbert, compressed));
+    assert!(fs::read_dir(&directory).unwrap().all(|entry| !entry.unwrap().file_name().to_string_lossy().contains("unsorted")));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
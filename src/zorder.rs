@@ -0,0 +1,96 @@
+//! Z-order (Morton) layout, an alternative to the Hilbert layout of `hilbert_curve`.
+//!
+//! The index of `(x, y)` interleaves their bits, with `x` taking the higher bit of each pair, so
+//! the upper 32 bits of an index interleave the upper 16 bits of `x` and `y`, exactly as Hilbert
+//! indices do. `convert_to_zorder` therefore produces the same upper/lower records as
+//! `convert_to_hilbert`, and `UpperLowerMemMapper` reads them; only the order of blocks and of
+//! edges within blocks differs, and computing it takes a few shifts and masks rather than tables.
+
+use std::collections::HashMap;
+use graph_iterator::EdgeMapper;
+use hilbert_curve::DenseNames;
+
+// spreads the bits of `x` out to the even bit positions.
+#[inline]
+fn spread(x: u32) -> u64 {
+    let mut x = x as u64;
+    x = (x | (x << 16)) & 0x0000FFFF0000FFFF;
+    x = (x | (x <<  8)) & 0x00FF00FF00FF00FF;
+    x = (x | (x <<  4)) & 0x0F0F0F0F0F0F0F0F;
+    x = (x | (x <<  2)) & 0x3333333333333333;
+    x = (x | (x <<  1)) & 0x5555555555555555;
+    x
+}
+
+// gathers the even bits of `x`, inverting `spread`.
+#[inline]
+fn compact(x: u64) -> u32 {
+    let mut x = x & 0x5555555555555555;
+    x = (x | (x >>  1)) & 0x3333333333333333;
+    x = (x | (x >>  2)) & 0x0F0F0F0F0F0F0F0F;
+    x = (x | (x >>  4)) & 0x00FF00FF00FF00FF;
+    x = (x | (x >>  8)) & 0x0000FFFF0000FFFF;
+    x = (x | (x >> 16)) & 0x00000000FFFFFFFF;
+    x as u32
+}
+
+/// The Z-order index of `(x, y)`.
+#[inline]
+pub fn entangle((x, y): (u32, u32)) -> u64 {
+    (spread(x) << 1) | spread(y)
+}
+
+/// The pair whose Z-order index is `tangle`.
+#[inline]
+pub fn detangle(tangle: u64) -> (u32, u32) {
+    (compact(tangle >> 1), compact(tangle))
+}
+
+#[test]
+fn test_entangle_detangle() {
+    let pairs = [(0, 0), (1, 0), (0, 1), (12345, 678910), (u32::MAX, 0), (0, u32::MAX), (u32::MAX, u32::MAX)];
+    for &pair in pairs.iter() {
+        assert_eq!(detangle(entangle(pair)), pair);
+    }
+    assert_eq!(entangle((1, 0)), 2);
+    assert_eq!(entangle((0, 1)), 1);
+    assert_eq!(entangle((0xFFFF0000, 0)) >> 32, entangle((0xFFFF, 0)));
+}
+
+/// Groups edges into 65536 x 65536 blocks in Z-order, handing each block's upper coordinates, edge
+/// count, and lower coordinates (themselves in Z-order) to `output`, as `convert_to_hilbert` does.
+pub fn convert_to_zorder<I, O>(graph: &I, make_dense: bool, mut output: O)
+where I : EdgeMapper,
+      O : FnMut(u16, u16, u32, &Vec<(u16, u16)>),
+{
+    let mut uppers: HashMap<u32,Vec<u32>> = HashMap::new();
+    let mut names = DenseNames::new();
+
+    graph.map_edges(|mut node, mut edge| {
+        if make_dense {
+            node = names.rename(node);
+            edge = names.rename(edge);
+        }
+
+        let entangled = entangle((node, edge));
+        uppers.entry((entangled >> 32) as u32).or_default().push(entangled as u32);
+    });
+
+    let mut keys: Vec<u32> = uppers.keys().cloned().collect();
+    keys.sort();
+
+    let mut temp = Vec::new();
+    for &upper in keys.iter() {
+        let mut lowers = uppers.remove(&upper).unwrap();
+        let (upperx, uppery) = detangle(upper as u64);
+
+        lowers.sort();
+        temp.clear();
+        for &lower in lowers.iter() {
+            let (lowerx, lowery) = detangle(lower as u64);
+            temp.push((lowerx as u16, lowery as u16));
+        }
+
+        output(upperx as u16, uppery as u16, lowers.len() as u32, &temp);
+    }
+}
@@ -0,0 +1,659 @@
+// Converts small generated graphs through to_vertex, to_hilbert, to_zorder, compressed parse_to_hilbert,
+// and compressed scan, and checks that every mapper sees the same edges and every algorithm reaches
+// the same result.
+
+extern crate byteorder;
+extern crate COST;
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use COST::compression::{self, Output};
+use COST::generators::{self, XorShift};
+use COST::graph_iterator::{EdgeMapper, DeltaCompressedReaderMapper, DeltaCompressedSliceMapper, NodesEdgesMemMapper, UpperLowerMemMapper, VarintMemMapper};
+use COST::graph_iterator::{NodeRecord, UpperRecord, LowerRecord};
+use COST::hilbert_curve::{encode, split_bytes, to_hilbert, Decoder, SPLIT_MAGIC};
+use COST::typedrw::TypedMemoryMap;
+
+// a fresh directory holding one graph in every format, removed when dropped; with `lz4` set,
+// every file on disk is LZ4-compressed, and named with a `.lz4` suffix.
+struct Formats {
+    directory:  PathBuf,
+    edges:      Vec<(u32, u32)>,
+    nodes:      u32,
+    lz4:        bool,
+}
+
+impl Formats {
+    fn new(name: &str, edges: Vec<(u32, u32)>) -> Formats {
+        Formats::with_lz4(name, edges, false)
+    }
+
+    fn with_lz4(name: &str, mut edges: Vec<(u32, u32)>, lz4: bool) -> Formats {
+        let directory = std::env::temp_dir().join(format!("cost-equivalence-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        // the compressed format cannot hold duplicates or the edge (0, 0), whose Hilbert index is zero.
+        edges.sort();
+        edges.dedup();
+        edges.retain(|&edge| edge != (0, 0));
+        let nodes = edges.iter().map(|&(x, y)| std::cmp::max(x, y) + 1).max().unwrap_or(0);
+
+        let formats = Formats { directory, edges, nodes, lz4 };
+
+        let mut text = Output::new(BufWriter::new(File::create(formats.text()).unwrap()), lz4).unwrap();
+        for &(src, dst) in &formats.edges { writeln!(text, "{} {}", src, dst).unwrap(); }
+        text.finish().unwrap();
+
+        let flags: &[&str] = if lz4 { &["--lz4"] } else { &[] };
+        let input_flags: &[&str] = if lz4 { &["--lz4-input"] } else { &[] };
+        run(env!("CARGO_BIN_EXE_to_vertex"), &[&[&formats.text()[..], &formats.prefix()], flags].concat());
+        run(env!("CARGO_BIN_EXE_to_hilbert"), &[&[&formats.prefix()[..]], flags].concat());
+        run(env!("CARGO_BIN_EXE_to_zorder"), &[&[&formats.prefix()[..]], flags].concat());
+
+        let status = Command::new(env!("CARGO_BIN_EXE_compressed"))
+            .arg("parse_to_hilbert")
+            .args(flags)
+            .args(input_flags)
+            .stdin(File::open(formats.text()).unwrap())
+            .stdout(File::create(formats.compressed()).unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success(), "compressed parse_to_hilbert failed");
+
+        // compressed scan wants the edges as a delta stream of (src << 32) + dst keys.
+        let mut sorted = Output::new(BufWriter::new(File::create(formats.sorted()).unwrap()), lz4).unwrap();
+        let mut prev = 0u64;
+        for &(src, dst) in &formats.edges {
+            let next = ((src as u64) << 32) + dst as u64;
+            encode(&mut sorted, next - prev);
+            prev = next;
+        }
+        sorted.finish().unwrap();
+
+        let status = Command::new(env!("CARGO_BIN_EXE_compressed"))
+            .args(["scan", "--offsets", &format!("{}.offsets", formats.varint())])
+            .args(flags)
+            .args(input_flags)
+            .stdin(File::open(formats.sorted()).unwrap())
+            .stdout(File::create(formats.varint()).unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success(), "compressed scan failed");
+
+        formats
+    }
+
+    fn text(&self) -> String { self.file("graph.txt") }
+    fn prefix(&self) -> String { self.directory.join("graph").to_string_lossy().into_owned() }
+    fn compressed(&self) -> String { self.file("graph.compressed") }
+    fn sorted(&self) -> String { self.file("graph.sorted") }
+    fn varint(&self) -> String { self.file("graph.varint") }
+
+    // the path of a file written outside the library writers, which name compressed files themselves.
+    fn file(&self, name: &str) -> String {
+        let suffix = if self.lz4 { ".lz4" } else { "" };
+        self.directory.join(format!("{}{}", name, suffix)).to_string_lossy().into_owned()
+    }
+
+    // (mode, path) pairs for the algorithm binaries.
+    fn modes(&self) -> Vec<(&'static str, String)> {
+        vec![("vertex", self.prefix()), ("hilbert", self.prefix()), ("zorder", self.prefix()), ("compressed", self.compressed()), ("compressed-mmap", self.compressed()), ("varint", self.varint())]
+    }
+
+    fn check_mappers(&self) {
+        assert_eq!(sorted_edges(&NodesEdgesMemMapper::new(&self.prefix()).unwrap()), self.edges, "vertex");
+        assert_eq!(sorted_edges(&UpperLowerMemMapper::new(&self.prefix()).unwrap()), self.edges, "hilbert");
+        let zorder = UpperLowerMemMapper::from_files(&format!("{}.zupper", self.prefix()), &format!("{}.zlower", self.prefix())).unwrap();
+        assert_eq!(sorted_edges(&zorder), self.edges, "zorder");
+        assert_eq!(sorted_edges(&DeltaCompressedReaderMapper::new(|| BufReader::new(compression::open(&self.compressed()).unwrap()))), self.edges, "compressed reader");
+        let bytes = TypedMemoryMap::<u8>::new(self.compressed()).unwrap();
+        assert_eq!(sorted_edges(&DeltaCompressedSliceMapper::new(&bytes[..]).unwrap()), self.edges, "compressed slice");
+
+        let varint = VarintMemMapper::new(&self.varint(), &format!("{}.offsets", self.varint())).unwrap();
+        assert_eq!(sorted_edges(&varint), self.edges, "varint");
+        for node in 0 .. self.nodes + 1 {
+            let expected: Vec<u32> = self.edges.iter().filter(|&&(src, _)| src == node).map(|&(_, dst)| dst).collect();
+            assert_eq!(varint.neighbors(node).collect::<Vec<_>>(), expected, "varint neighbors of {}", node);
+        }
+    }
+}
+
+impl Drop for Formats {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn sorted_edges<G: EdgeMapper>(graph: &G) -> Vec<(u32, u32)> {
+    let mut edges = Vec::new();
+    graph.map_edges(|x, y| edges.push((x, y)));
+    edges.sort();
+    edges
+}
+
+fn run(binary: &str, args: &[&str]) -> String {
+    String::from_utf8(run_bytes(binary, args)).unwrap()
+}
+
+fn run_bytes(binary: &str, args: &[&str]) -> Vec<u8> {
+    let output = Command::new(binary).args(args).stderr(Stdio::inherit()).output().unwrap();
+    assert!(output.status.success(), "{} {:?} failed", binary, args);
+    output.stdout
+}
+
+// the `result` object of a JSON record printed with `--format json`; it is always the last field.
+fn result(record: &str) -> String {
+    let start = record.find("\"result\":").expect("record without result");
+    record[start ..].trim().to_owned()
+}
+
+// output lines that carry results rather than timings.
+fn untimed(output: &str) -> Vec<String> {
+    output.lines().filter(|line| !line.contains('\t') && !line.starts_with("Iteration")).map(|line| line.to_owned()).collect()
+}
+
+#[test]
+fn rmat_formats_agree() {
+    let mut edges = Vec::new();
+    generators::rmat(10, 5000, (0.57, 0.19, 0.19), &mut XorShift::new(0), |x, y| edges.push((x, y)));
+    Formats::new("rmat", edges).check_mappers();
+}
+
+#[test]
+fn erdos_renyi_formats_agree() {
+    let mut edges = Vec::new();
+    generators::erdos_renyi(1000, 5000, &mut XorShift::new(1), |x, y| edges.push((x, y)));
+    Formats::new("erdos_renyi", edges).check_mappers();
+}
+
+#[test]
+fn grid_formats_agree() {
+    let mut edges = Vec::new();
+    generators::grid(30, 40, |x, y| edges.push((x, y)));
+    Formats::new("grid", edges).check_mappers();
+}
+
+#[test]
+fn power_law_formats_agree() {
+    let mut edges = Vec::new();
+    generators::power_law(1000, 5000, 2.5, &mut XorShift::new(2), |x, y| edges.push((x, y)));
+    Formats::new("power_law", edges).check_mappers();
+}
+
+#[test]
+fn lz4_formats_agree() {
+    let mut edges = Vec::new();
+    generators::rmat(10, 5000, (0.57, 0.19, 0.19), &mut XorShift::new(4), |x, y| edges.push((x, y)));
+    let formats = Formats::with_lz4("lz4", edges, true);
+    for file in &[formats.text(), formats.compressed(), format!("{}.nodes.lz4", formats.prefix()), format!("{}.upper.lz4", formats.prefix())] {
+        assert!(fs::read(file).unwrap().starts_with(&[0x04, 0x22, 0x4D, 0x18]), "{} is not LZ4-compressed", file);
+    }
+    assert!(fs::metadata(format!("{}.nodes", formats.prefix())).is_err(), "uncompressed nodes written");
+    formats.check_mappers();
+
+    let nodes = formats.nodes.to_string();
+    let results: Vec<String> = formats.modes().iter().map(|&(mode, ref path)| {
+        result(&run(env!("CARGO_BIN_EXE_union_find"), &[mode, path, &nodes, "--format", "json"]))
+    }).collect();
+    assert!(results.iter().all(|r| r == &results[0]), "union_find: {:?}", results);
+}
+
+#[test]
+fn lz4_is_chosen_by_name() {
+    let directory = std::env::temp_dir().join(format!("cost-equivalence-{}-magic", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let path = |name: &str| directory.join(name).to_string_lossy().into_owned();
+    let edges = |output: &str| output.lines().find(|line| line.starts_with("edges: ")).map(|line| line.to_owned());
+
+    // the first edge's target, 407708164, is written as 04 22 4D 18: the LZ4 magic number.
+    fs::write(path("magic.txt"), "0 407708164\n0 5\n1 2\n").unwrap();
+    run(env!("CARGO_BIN_EXE_to_vertex"), &[&path("magic.txt"), &path("magic")]);
+    assert!(fs::read(path("magic.edges")).unwrap().starts_with(&[0x04, 0x22, 0x4D, 0x18]));
+    let output = run(env!("CARGO_BIN_EXE_stats"), &["vertex", &path("magic")]);
+    assert_eq!(edges(&output), Some("edges: 3".to_owned()), "{}", output);
+
+    // as are four one-byte deltas of a compressed graph.
+    fs::write(path("magic.compressed"), [0x04, 0x22, 0x4D, 0x18]).unwrap();
+    for &mode in &["compressed", "compressed-mmap"] {
+        let output = run(env!("CARGO_BIN_EXE_stats"), &[mode, &path("magic.compressed")]);
+        assert_eq!(edges(&output), Some("edges: 4".to_owned()), "{}: {}", mode, output);
+    }
+
+    // an LZ4 file that fails to decompress partway is an error, rather than a shorter graph.
+    let mut writer = Output::new(BufWriter::new(File::create(path("corrupt.compressed.lz4")).unwrap()), true).unwrap();
+    let mut prev = 0u64;
+    to_hilbert(&(1 .. 100000u32).map(|x| (x, x.wrapping_mul(2654435761) % 100000)).collect::<Vec<_>>(), |next| {
+        encode(&mut writer, next - prev);
+        prev = next;
+    });
+    writer.finish().unwrap();
+    let mut bytes = fs::read(path("corrupt.compressed.lz4")).unwrap();
+    let middle = bytes.len() / 2;
+    for byte in &mut bytes[middle .. middle + 64] { *byte = 0xff; }
+    fs::write(path("corrupt.compressed.lz4"), &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_stats")).args(["compressed", &path("corrupt.compressed.lz4")]).output().unwrap();
+    assert!(!output.status.success(), "corrupt graph read: {}", String::from_utf8_lossy(&output.stdout));
+
+    let _ = fs::remove_dir_all(&directory);
+}
+
+// bfs_cc2012 is left out: it is specific to the common crawl dataset, and allocates accordingly.
+#[test]
+fn algorithms_agree_across_modes() {
+    let mut edges = Vec::new();
+    generators::rmat(9, 3000, (0.57, 0.19, 0.19), &mut XorShift::new(3), |x, y| edges.push((x, y)));
+    let formats = Formats::new("algorithms", edges);
+    let nodes = formats.nodes.to_string();
+
+    let json: &[(&str, &[&str])] = &[
+        ("union_find", &[]),
+        ("label_prop", &[]),
+        ("label_prop", &["--community", "10"]),
+    ];
+    for &(binary, extra) in json {
+        let results: Vec<String> = formats.modes().iter().map(|&(mode, ref path)| {
+            let mut args = vec![mode, path, &nodes[..], "--format", "json"];
+            args.extend_from_slice(extra);
+            result(&run(&binary_path(binary), &args))
+        }).collect();
+        assert!(results.iter().all(|r| r == &results[0]), "{} {:?}: {:?}", binary, extra, results);
+    }
+
+    let stats: Vec<String> = formats.modes().iter().map(|&(mode, ref path)| {
+        result(&run(env!("CARGO_BIN_EXE_stats"), &[mode, path, "--format", "json"]))
+    }).collect();
+    assert!(stats.iter().all(|r| r == &stats[0]), "stats: {:?}", stats);
+
+    for &binary in &["anf", "coloring", "mis"] {
+        let outputs: Vec<Vec<String>> = formats.modes().iter().map(|&(mode, ref path)| {
+            untimed(&run(&binary_path(binary), &[mode, path, &nodes]))
+        }).collect();
+        assert!(outputs.iter().all(|o| o == &outputs[0]), "{}: {:?}", binary, outputs);
+    }
+
+    // floating point sums depend on edge order, so pagerank is compared approximately.
+    let ranks: Vec<f64> = formats.modes().iter().map(|&(mode, ref path)| {
+        let record = run(env!("CARGO_BIN_EXE_pagerank"), &[mode, path, &nodes, "--format", "json"]);
+        let start = record.find("\"max_rank\":").unwrap() + "\"max_rank\":".len();
+        record[start ..].trim_end().trim_end_matches('}').parse().unwrap()
+    }).collect();
+    assert!(ranks.iter().all(|r| (r - ranks[0]).abs() < 1e-3 * ranks[0].abs()), "pagerank: {:?}", ranks);
+}
+
+#[test]
+fn louvain_separates_two_cliques() {
+    // two 6-cliques joined by the single edge (5, 6).
+    let mut edges = vec![(5, 6)];
+    for clique in 0 .. 2 {
+        for x in 0 .. 6 {
+            for y in x + 1 .. 6 { edges.push((6 * clique + x, 6 * clique + y)); }
+        }
+    }
+    let formats = Formats::new("louvain", edges);
+    let output = formats.directory.join("communities").to_string_lossy().into_owned();
+    run(env!("CARGO_BIN_EXE_louvain"), &[&formats.prefix(), "12", &output]);
+
+    let mut reader = BufReader::new(File::open(&output).unwrap());
+    let labels: Vec<u32> = (0 .. 12).map(|_| reader.read_u32::<LittleEndian>().unwrap()).collect();
+    assert!(labels[.. 6].iter().all(|&l| l == labels[0]), "{:?}", labels);
+    assert!(labels[6 ..].iter().all(|&l| l == labels[6]), "{:?}", labels);
+    assert_ne!(labels[0], labels[6], "{:?}", labels);
+}
+
+#[test]
+fn hits_scores_a_bipartite_graph() {
+    // hubs 0, 1, 2 point at authorities 3 and 4. The authority scores are the principal eigenvector
+    // of [[3, 1], [1, 1]], (cos 22.5°, sin 22.5°), and the hub scores are proportional to the sums
+    // of the authorities each hub points at.
+    let formats = Formats::new("hits", vec![(0, 3), (0, 4), (1, 3), (2, 3)]);
+    let angle = std::f32::consts::PI / 8.0;
+    let expected_hubs = [0.5f32.sqrt(), 0.5, 0.5, 0.0, 0.0];
+    let expected_authorities = [0.0, 0.0, 0.0, angle.cos(), angle.sin()];
+
+    let scores = |path: &str| -> Vec<f32> {
+        let mut reader = BufReader::new(File::open(path).unwrap());
+        (0 .. 5).map(|_| reader.read_f32::<LittleEndian>().unwrap()).collect()
+    };
+    let close = |scores: &[f32], expected: &[f32]| scores.iter().zip(expected).all(|(x, y)| (x - y).abs() < 1e-5);
+    for &(mode, ref path) in &formats.modes() {
+        let output = formats.directory.join(format!("hits-{}", mode)).to_string_lossy().into_owned();
+        run(env!("CARGO_BIN_EXE_hits"), &[mode, path, "5", &output]);
+        let hubs = scores(&format!("{}.hubs", output));
+        let authorities = scores(&format!("{}.authorities", output));
+        assert!(close(&hubs, &expected_hubs), "{}: hubs {:?}", mode, hubs);
+        assert!(close(&authorities, &expected_authorities), "{}: authorities {:?}", mode, authorities);
+    }
+}
+
+#[test]
+fn betweenness_of_a_path_is_exact() {
+    // on the undirected path 0 - 1 - 2 - 3 - 4, vertex k lies on the shortest paths between the
+    // k vertices on one side and the 4 - k on the other, in both directions. With every vertex
+    // a source, the estimate is exact.
+    let mut edges = Vec::new();
+    for x in 0 .. 4 { edges.push((x, x + 1)); edges.push((x + 1, x)); }
+    let formats = Formats::new("betweenness", edges);
+    let output = run(env!("CARGO_BIN_EXE_betweenness"), &[&formats.prefix(), "5", "5", "7", "5"]);
+
+    let ranked: Vec<(u32, f64)> = output.lines().filter_map(|line| {
+        let mut fields = line.split('\t');
+        match (fields.next().and_then(|f| f.parse().ok()), fields.next().and_then(|f| f.parse().ok())) {
+            (Some(node), Some(score)) => Some((node, score)),
+            _ => None,
+        }
+    }).collect();
+    assert_eq!(ranked, vec![(2, 8.0), (1, 6.0), (3, 6.0), (0, 0.0), (4, 0.0)], "{}", output);
+}
+
+#[test]
+fn generate_drops_edges_only_for_compressed() {
+    // four vertices and a hundred edges make duplicates certain.
+    let formats = Formats::new("generate", vec![(0, 1)]);
+    let text = formats.directory.join("generated.txt").to_string_lossy().into_owned();
+    let compressed = formats.directory.join("generated.bin").to_string_lossy().into_owned();
+
+    run(env!("CARGO_BIN_EXE_generate"), &["rmat", "2", "100", "--seed", "3", "text", &text]);
+    let mut edges: Vec<(u32, u32)> = fs::read_to_string(&text).unwrap().lines().map(|line| {
+        let mut fields = line.split(' ').map(|field| field.parse().unwrap());
+        (fields.next().unwrap(), fields.next().unwrap())
+    }).collect();
+    assert_eq!(edges.len(), 100);
+
+    edges.sort();
+    edges.dedup();
+    edges.retain(|&edge| edge != (0, 0));
+    let output = run(env!("CARGO_BIN_EXE_generate"), &["rmat", "2", "100", "--seed", "3", "compressed", &compressed]);
+    assert_eq!(output.trim(), format!("dropped {} duplicate edges and self-loops at zero", 100 - edges.len()));
+    assert_eq!(sorted_edges(&DeltaCompressedReaderMapper::new(|| BufReader::new(File::open(&compressed).unwrap()))), edges);
+}
+
+#[test]
+fn range_queries_match_filtering() {
+    // scale 18 spreads the edges over sixteen 65536 x 65536 blocks.
+    let mut edges = Vec::new();
+    generators::rmat(18, 5000, (0.4, 0.2, 0.2), &mut XorShift::new(6), |x, y| edges.push((x, y)));
+    let formats = Formats::new("ranges", edges);
+
+    let hilbert = UpperLowerMemMapper::new(&formats.prefix()).unwrap();
+    let zorder = UpperLowerMemMapper::from_files(&format!("{}.zupper", formats.prefix()), &format!("{}.zlower", formats.prefix())).unwrap();
+    let ranges = [0 ..= u32::MAX, 0 ..= 65535, 65536 ..= 131071, 1000 ..= 70000, 100000 ..= 200000, 5 ..= 5];
+    for sources in ranges.iter() {
+        for targets in ranges.iter() {
+            let expected: Vec<(u32, u32)> = formats.edges.iter().cloned().filter(|&(x, y)| sources.contains(&x) && targets.contains(&y)).collect();
+            for graph in &[&hilbert, &zorder] {
+                assert_eq!(sorted_edges(&graph.restrict(sources.clone(), targets.clone())), expected, "{:?} x {:?}", sources, targets);
+            }
+        }
+    }
+}
+
+#[test]
+fn hilbert_splits_agree() {
+    let mut edges = Vec::new();
+    generators::rmat(18, 5000, (0.4, 0.2, 0.2), &mut XorShift::new(7), |x, y| edges.push((x, y)));
+    let formats = Formats::new("splits", edges);
+    let upper = format!("{}.upper", formats.prefix());
+    let lower = format!("{}.lower", formats.prefix());
+
+    let record = run(env!("CARGO_BIN_EXE_stats"), &["hilbert", &formats.prefix(), "--splits", "--format", "json"]);
+    let sizes = split_bytes(&UpperLowerMemMapper::new(&formats.prefix()).unwrap()).unwrap();
+    assert!(record.contains("\"suggested_split\":"), "stats --splits: {}", record);
+    assert!(split_bytes(&NodesEdgesMemMapper::new(&formats.prefix()).unwrap()).is_err(), "split sizes from unordered edges");
+
+    let ranges = [0 ..= u32::MAX, 1000 ..= 70000, 100000 ..= 200000];
+    for &low_bits in &[8, 12, 20, 24, 16] {
+        run(env!("CARGO_BIN_EXE_to_hilbert"), &[&formats.prefix(), "--split", &low_bits.to_string(), "--force"]);
+        assert_eq!(fs::read(&upper).unwrap().starts_with(&SPLIT_MAGIC), low_bits != 16, "header for {} low bits", low_bits);
+
+        let graph = UpperLowerMemMapper::new(&formats.prefix()).unwrap();
+        assert_eq!(graph.low_bits(), low_bits);
+        assert_eq!(sorted_edges(&graph), formats.edges, "{} low bits", low_bits);
+        for sources in ranges.iter() {
+            let expected: Vec<(u32, u32)> = formats.edges.iter().cloned().filter(|&(x, y)| sources.contains(&x) && sources.contains(&y)).collect();
+            assert_eq!(sorted_edges(&graph.restrict(sources.clone(), sources.clone())), expected, "{} low bits, {:?}", low_bits, sources);
+        }
+
+        let bytes = fs::metadata(&upper).unwrap().len() + fs::metadata(&lower).unwrap().len();
+        assert_eq!(sizes.iter().find(|&&(bits, _)| bits == low_bits), Some(&(low_bits, bytes)), "{} low bits", low_bits);
+    }
+}
+
+#[test]
+fn outputs_need_force() {
+    let mut edges = Vec::new();
+    generators::erdos_renyi(100, 500, &mut XorShift::new(8), |x, y| edges.push((x, y)));
+    let formats = Formats::new("force", edges);
+    let prefix = formats.prefix();
+    let text = formats.text();
+
+    let files = |directory: &PathBuf| -> Vec<(String, Vec<u8>)> {
+        let mut files: Vec<_> = fs::read_dir(directory).unwrap().map(|entry| {
+            let path = entry.unwrap().path();
+            (path.to_string_lossy().into_owned(), fs::read(&path).unwrap())
+        }).collect();
+        files.sort();
+        files
+    };
+    let before = files(&formats.directory);
+
+    // without --force, every tool refuses to replace an existing graph and leaves no files behind.
+    let refusals: &[(&str, Vec<&str>)] = &[
+        (env!("CARGO_BIN_EXE_to_vertex"), vec![&text, &prefix]),
+        (env!("CARGO_BIN_EXE_to_hilbert"), vec![&prefix]),
+        (env!("CARGO_BIN_EXE_to_zorder"), vec![&prefix]),
+        (env!("CARGO_BIN_EXE_generate"), vec!["grid", "3", "3", "hilbert", &prefix]),
+    ];
+    for &(binary, ref args) in refusals {
+        let output = Command::new(binary).args(args).output().unwrap();
+        assert!(!output.status.success(), "{} {:?} overwrote its outputs", binary, args);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--force"), "{} {:?}", binary, args);
+        assert!(output.stdout.is_empty(), "{} {:?}", binary, args);
+        assert_eq!(files(&formats.directory), before, "{} {:?}", binary, args);
+    }
+
+    // with --force, they replace the graph with an identical one.
+    run(env!("CARGO_BIN_EXE_to_vertex"), &[&text, &prefix, "--force"]);
+    run(env!("CARGO_BIN_EXE_to_hilbert"), &[&prefix, "--force"]);
+    run(env!("CARGO_BIN_EXE_to_zorder"), &[&prefix, "--force"]);
+    assert_eq!(files(&formats.directory), before);
+    formats.check_mappers();
+}
+
+#[test]
+fn parallel_merge_matches_serial() {
+    let directory = std::env::temp_dir().join(format!("cost-equivalence-{}-merge", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+
+    // overlapping shards, one of them LZ4-compressed.
+    let mut rng = XorShift::new(5);
+    let mut union = Vec::new();
+    let mut shards = Vec::new();
+    for shard in 0 .. 5 {
+        let mut edges = Vec::new();
+        generators::erdos_renyi(2000, 20000, &mut rng, |x, y| edges.push((x, y)));
+        edges.sort();
+        edges.dedup();
+        edges.retain(|&edge| edge != (0, 0));
+
+        let suffix = if shard == 0 { ".lz4" } else { "" };
+        let path = directory.join(format!("shard-{}{}", shard, suffix)).to_string_lossy().into_owned();
+        let mut writer = Output::new(BufWriter::new(File::create(&path).unwrap()), shard == 0).unwrap();
+        let mut prev = 0u64;
+        to_hilbert(&edges, |next| { encode(&mut writer, next - prev); prev = next; union.push(next); });
+        writer.finish().unwrap();
+        shards.push(path);
+    }
+    union.sort();
+    union.dedup();
+
+    let shards: Vec<&str> = shards.iter().map(|s| &s[..]).collect();
+    let serial = run_bytes(env!("CARGO_BIN_EXE_compressed"), &[&["merge"], &shards[..]].concat());
+    let parallel = run_bytes(env!("CARGO_BIN_EXE_compressed"), &[&["merge", "--threads", "4"], &shards[..]].concat());
+    let _ = fs::remove_dir_all(&directory);
+
+    assert_eq!(Decoder::new(&serial[..]).collect::<Vec<_>>(), union);
+    assert_eq!(parallel, serial);
+}
+
+#[test]
+fn written_files_round_trip() {
+    let mut edges = Vec::new();
+    generators::rmat(18, 3000, (0.4, 0.2, 0.2), &mut XorShift::new(9), |x, y| edges.push((x, y)));
+    let formats = Formats::new("round_trip", edges);
+    let prefix = formats.prefix();
+
+    assert_eq!(std::mem::size_of::<NodeRecord>(), 8);
+    assert_eq!(std::mem::size_of::<UpperRecord>(), 8);
+    assert_eq!(std::mem::size_of::<LowerRecord>(), 4);
+
+    // the files' bytes, decoded as little-endian fields independently of the readers.
+    let fields = |name: &str, widths: &[usize]| -> Vec<Vec<u64>> {
+        let bytes = fs::read(format!("{}.{}", prefix, name)).unwrap();
+        let mut reader = &bytes[..];
+        let mut records = Vec::new();
+        while !reader.is_empty() {
+            records.push(widths.iter().map(|&width| reader.read_uint::<LittleEndian>(width).unwrap()).collect());
+        }
+        records
+    };
+
+    let nodes = fields("nodes", &[4, 4]);
+    let targets = fields("edges", &[4]);
+    let mut decoded = Vec::new();
+    let mut targets_iter = targets.iter();
+    for record in &nodes {
+        for _ in 0 .. record[1] { decoded.push((record[0] as u32, targets_iter.next().unwrap()[0] as u32)); }
+    }
+    assert_eq!(decoded, formats.edges);
+    let mapped = TypedMemoryMap::<NodeRecord>::new_le(format!("{}.nodes", prefix)).unwrap();
+    assert_eq!(mapped[..].iter().map(|r| vec![r.node as u64, r.count as u64]).collect::<Vec<_>>(), nodes);
+    let mapped = TypedMemoryMap::<u32>::new_le(format!("{}.edges", prefix)).unwrap();
+    assert_eq!(mapped[..].iter().map(|&t| vec![t as u64]).collect::<Vec<_>>(), targets);
+
+    let uppers = fields("upper", &[2, 2, 4]);
+    let lowers = fields("lower", &[2, 2]);
+    let mut decoded = Vec::new();
+    let mut lowers_iter = lowers.iter();
+    for record in &uppers {
+        for _ in 0 .. record[2] {
+            let lower = lowers_iter.next().unwrap();
+            decoded.push((((record[0] << 16) | lower[0]) as u32, ((record[1] << 16) | lower[1]) as u32));
+        }
+    }
+    decoded.sort();
+    assert_eq!(decoded, formats.edges);
+    let mapped = TypedMemoryMap::<UpperRecord>::new_le(format!("{}.upper", prefix)).unwrap();
+    assert_eq!(mapped[..].iter().map(|r| vec![r.x as u64, r.y as u64, r.count as u64]).collect::<Vec<_>>(), uppers);
+    let mapped = TypedMemoryMap::<LowerRecord>::new_le(format!("{}.lower", prefix)).unwrap();
+    assert_eq!(mapped[..].iter().map(|r| vec![r.x as u64, r.y as u64]).collect::<Vec<_>>(), lowers);
+}
+
+#[test]
+fn unreadable_graphs_report_their_files() {
+    let mut edges = Vec::new();
+    generators::grid(5, 5, |x, y| edges.push((x, y)));
+    let formats = Formats::new("unreadable", edges);
+    let prefix = formats.prefix();
+
+    let missing = format!("{}-missing", prefix);
+    let error = NodesEdgesMemMapper::new(&missing).err().expect("missing graph opened");
+    assert!(error.to_string().contains(&format!("{}.nodes", missing)), "{}", error);
+    assert!(UpperLowerMemMapper::new(&missing).is_err());
+    assert!(VarintMemMapper::new(&missing, &format!("{}.offsets", missing)).is_err());
+
+    // the binaries report the same error and exit, rather than panicking.
+    let output = Command::new(env!("CARGO_BIN_EXE_pagerank")).args(["vertex", &missing, "25"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("pagerank: ") && stderr.contains(&format!("{}.nodes", missing)), "{}", stderr);
+
+    // a truncated record, an unreadable split header, or blocks disagreeing with their edges, are
+    // refused rather than silently misread.
+    let upper_name = format!("{}.upper", prefix);
+    let lower_name = format!("{}.lower", prefix);
+    run(env!("CARGO_BIN_EXE_to_hilbert"), &[&prefix, "--split", "12", "--force"]);
+    let split_lower = fs::read(&lower_name).unwrap();
+    run(env!("CARGO_BIN_EXE_to_hilbert"), &[&prefix, "--split", "20", "--force"]);
+    fs::write(&lower_name, &split_lower).unwrap();
+    assert!(UpperLowerMemMapper::new(&prefix).is_err(), "lower records of another split opened");
+    run(env!("CARGO_BIN_EXE_to_hilbert"), &[&prefix, "--force"]);
+
+    let edges_name = format!("{}.edges", prefix);
+    let mut bytes = fs::read(&edges_name).unwrap();
+    bytes.pop();
+    fs::write(&edges_name, &bytes).unwrap();
+    let error = NodesEdgesMemMapper::new(&prefix).err().expect("truncated graph opened");
+    assert!(error.to_string().contains(&edges_name), "{}", error);
+
+    let mut bytes = fs::read(&lower_name).unwrap();
+    bytes.truncate(bytes.len() - 4);
+    fs::write(&lower_name, &bytes).unwrap();
+    let error = UpperLowerMemMapper::new(&prefix).err().expect("mismatched layout opened");
+    assert!(error.to_string().contains(&upper_name), "{}", error);
+
+    let mut header = SPLIT_MAGIC.to_vec();
+    header.extend_from_slice(&[40, 0, 0, 0, 0, 0, 0, 0]);
+    fs::write(&upper_name, &header).unwrap();
+    assert!(UpperLowerMemMapper::new(&prefix).is_err());
+}
+
+#[test]
+fn truncated_slices_are_refused() {
+    // a zero byte announces a multi-byte delta that never arrives.
+    for bytes in &[&[3u8, 0][..], &[3u8, 0, 0][..], &[3u8, 0, 7][..], &[0u8, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1][..]] {
+        let error = DeltaCompressedSliceMapper::new(bytes).err().expect("truncated slice accepted");
+        assert!(error.to_string().contains("compressed graph truncated"), "{}", error);
+    }
+    // deltas wider than 64 bits cannot be decoded either.
+    assert!(DeltaCompressedSliceMapper::new(&[0u8, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1][..]).is_err());
+    assert!(DeltaCompressedSliceMapper::new(&[3u8, 0, 1, 2, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1][..]).is_ok());
+}
+
+#[test]
+fn malformed_varint_lists_are_refused() {
+    let directory = std::env::temp_dir().join(format!("cost-equivalence-{}-varint", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let data_name = directory.join("graph.varint").to_string_lossy().into_owned();
+    let offsets_name = format!("{}.offsets", data_name);
+    let open = |data: &[u8], offsets: &[u64]| {
+        fs::write(&data_name, data).unwrap();
+        fs::write(&offsets_name, offsets.iter().flat_map(|offset| offset.to_le_bytes()).collect::<Vec<_>>()).unwrap();
+        VarintMemMapper::new(&data_name, &offsets_name)
+    };
+
+    let graph = open(&[1, 2, 0x81, 0x01], &[2]).unwrap();
+    assert_eq!(sorted_edges(&graph), vec![(0, 1), (0, 3), (1, 129)]);
+
+    // a list truncated mid-neighbor, as when the data file is cut short.
+    let error = open(&[1, 2, 0x81], &[2]).err().expect("truncated list accepted");
+    assert!(error.to_string().contains(&data_name) && error.to_string().contains("mid-neighbor"), "{}", error);
+    // offsets past the end of the data, or decreasing.
+    let error = open(&[1, 2], &[5]).err().expect("offsets past the data accepted");
+    assert!(error.to_string().contains(&offsets_name), "{}", error);
+    assert!(open(&[1, 2, 3], &[2, 1]).is_err());
+    // neighbors longer than five bytes, or beyond 32 bits.
+    assert!(open(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01], &[]).is_err());
+    assert!(open(&[0xff, 0xff, 0xff, 0xff, 0x0f, 0x01], &[]).is_err());
+    assert!(open(&[0xfe, 0xff, 0xff, 0xff, 0x0f, 0x01], &[]).is_ok());
+
+    let _ = fs::remove_dir_all(&directory);
+}
+
+fn binary_path(binary: &str) -> String {
+    match binary {
+        "union_find" => env!("CARGO_BIN_EXE_union_find"),
+        "label_prop" => env!("CARGO_BIN_EXE_label_prop"),
+        "anf" => env!("CARGO_BIN_EXE_anf"),
+        "coloring" => env!("CARGO_BIN_EXE_coloring"),
+        "mis" => env!("CARGO_BIN_EXE_mis"),
+        _ => panic!("unknown binary: {:?}", binary),
+    }.to_owned()
+}